@@ -0,0 +1,40 @@
+use std::time::Duration;
+
+use rustea::{command, App, Command, Message};
+
+struct Model {
+    remaining: u32,
+}
+
+struct TickMessage;
+
+fn tick() -> Command {
+    command::every(Duration::from_secs(1), |_| Box::new(TickMessage))
+}
+
+impl App for Model {
+    fn init(&self) -> Option<Command> {
+        Some(tick())
+    }
+
+    fn update(&mut self, msg: Message) -> Option<Command> {
+        if msg.is::<TickMessage>() {
+            self.remaining -= 1;
+            if self.remaining == 0 {
+                return Some(Box::new(command::quit));
+            }
+
+            return Some(tick());
+        }
+
+        None
+    }
+
+    fn view(&self) -> String {
+        format!("Quitting in {}...", self.remaining)
+    }
+}
+
+fn main() {
+    rustea::run(Model { remaining: 3 }).unwrap();
+}
@@ -0,0 +1,52 @@
+use std::time::Duration;
+
+use rustea::{command, App, Command, Message, Program};
+
+const FILES: [&str; 4] = ["foo.tar.gz", "bar.zip", "baz.deb", "qux.rpm"];
+
+struct Model {
+    done: usize,
+}
+
+struct DownloadedMessage;
+
+fn download() -> Command {
+    command::tick(Duration::from_millis(500), |_| Box::new(DownloadedMessage))
+}
+
+impl App for Model {
+    fn init(&self) -> Option<Command> {
+        Some(download())
+    }
+
+    fn update(&mut self, msg: Message) -> Option<Command> {
+        if msg.is::<DownloadedMessage>() {
+            let file = FILES[self.done];
+            self.done += 1;
+
+            let next = if self.done == FILES.len() {
+                Box::new(command::quit)
+            } else {
+                download()
+            };
+            // the log line stays in the scrollback, above the progress
+            return Some(command::sequence(vec![
+                command::printf(format_args!("✓ downloaded {}", file)),
+                next,
+            ]));
+        }
+
+        None
+    }
+
+    fn view(&self) -> String {
+        match FILES.get(self.done) {
+            Some(file) => format!("Downloading {} ({}/{})", file, self.done + 1, FILES.len()),
+            None => "All done!".to_string(),
+        }
+    }
+}
+
+fn main() {
+    Program::new(Model { done: 0 }).inline(true).run().unwrap();
+}
@@ -0,0 +1,56 @@
+use std::{env, fs, process};
+
+use rustea::{
+    command::{self, ExecFinished},
+    crossterm::event::{KeyCode, KeyEvent, KeyModifiers},
+    App, Command, Message,
+};
+
+struct Model {
+    path: String,
+    status: String,
+}
+
+impl App for Model {
+    fn update(&mut self, msg: Message) -> Option<Command> {
+        if let Some(key_event) = msg.downcast_ref::<KeyEvent>() {
+            match key_event.code {
+                KeyCode::Char('c') if key_event.modifiers == KeyModifiers::CONTROL => {
+                    return Some(Box::new(command::quit));
+                }
+                KeyCode::Char('e') => {
+                    let editor = env::var("EDITOR").unwrap_or_else(|_| "vi".to_string());
+                    let mut editor = process::Command::new(editor);
+                    editor.arg(&self.path);
+                    // the editor gets the terminal, until it exits
+                    return Some(command::exec(editor));
+                }
+                _ => (),
+            }
+        } else if let Ok(finished) = msg.downcast::<ExecFinished>() {
+            self.status = match finished.status {
+                Ok(status) => format!("Editor exited with {}", status),
+                Err(err) => format!("Couldn't start the editor: {}", err),
+            };
+        }
+
+        None
+    }
+
+    fn view(&self) -> String {
+        let contents = fs::read_to_string(&self.path).unwrap_or_default();
+        format!(
+            "{}\n\n{}\n\nPress e to edit {}, or ctrl+c to quit.",
+            self.status, contents, self.path
+        )
+    }
+}
+
+fn main() {
+    let path = env::temp_dir().join("rustea_notes.txt");
+    rustea::run(Model {
+        path: path.display().to_string(),
+        status: "Nothing edited yet.".to_string(),
+    })
+    .unwrap();
+}
@@ -11,6 +11,7 @@ impl App for Model {
             if let KeyModifiers::CONTROL = key_event.modifiers {
                 match key_event.code {
                     KeyCode::Char('c') => return Some(Box::new(command::quit)),
+                    KeyCode::Char('z') => return Some(Box::new(command::suspend)),
                     _ => return None,
                 }
             }
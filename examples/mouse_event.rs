@@ -1,7 +1,7 @@
 use rustea::{
     command::quit,
     crossterm::event::{MouseEvent, MouseEventKind},
-    App, Command, Message,
+    App, Command, Message, MouseMode, Program,
 };
 
 struct Model {
@@ -33,6 +33,8 @@ impl App for Model {
 fn main() {
     let model = Model { col: 0, row: 0 };
 
-    rustea::enable_mouse_capture().unwrap();
-    rustea::run(model).unwrap();
+    Program::new(model)
+        .mouse(MouseMode::AllMotion)
+        .run()
+        .unwrap();
 }
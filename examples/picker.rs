@@ -0,0 +1,58 @@
+use rustea::{
+    command,
+    crossterm::event::{KeyCode, KeyEvent, KeyModifiers},
+    App, Command, Message, Program,
+};
+
+const CHOICES: [&str; 3] = ["Tea", "Coffee", "Water"];
+
+struct Model {
+    cursor: usize,
+    selected: Option<&'static str>,
+}
+
+impl App for Model {
+    fn update(&mut self, msg: Message) -> Option<Command> {
+        if let Some(key_event) = msg.downcast_ref::<KeyEvent>() {
+            match key_event.code {
+                KeyCode::Char('c') if key_event.modifiers == KeyModifiers::CONTROL => {
+                    return Some(command::quit_with(1));
+                }
+                KeyCode::Up => self.cursor = self.cursor.saturating_sub(1),
+                KeyCode::Down => self.cursor = (self.cursor + 1).min(CHOICES.len() - 1),
+                KeyCode::Enter => {
+                    self.selected = Some(CHOICES[self.cursor]);
+                    return Some(Box::new(command::quit));
+                }
+                _ => (),
+            }
+        }
+
+        None
+    }
+
+    fn view(&self) -> String {
+        let mut out = "What would you like to drink?\n\n".to_string();
+        for (i, choice) in CHOICES.iter().enumerate() {
+            let cursor = if i == self.cursor { ">" } else { " " };
+            out.push_str(&format!("{} {}\n", cursor, choice));
+        }
+        out
+    }
+}
+
+fn main() {
+    // render below the shell prompt, instead of taking over the screen
+    let exit = Program::new(Model {
+        cursor: 0,
+        selected: None,
+    })
+    .inline(true)
+    .run()
+    .unwrap();
+
+    match exit.app.selected {
+        Some(choice) => println!("One {}, coming up!", choice),
+        None => std::process::exit(exit.code),
+    }
+}
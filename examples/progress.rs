@@ -0,0 +1,80 @@
+use std::{thread, time::Duration};
+
+use rustea::{
+    command,
+    crossterm::event::{KeyCode, KeyEvent, KeyModifiers},
+    App, Command, Message,
+};
+
+struct Model {
+    percent: u32,
+    done: bool,
+}
+
+struct ProgressMessage(u32);
+struct DoneMessage;
+
+fn download() -> Command {
+    let stream = command::stream(|emitter| {
+        for percent in 0..=100 {
+            // stops early once a newer download supersedes this one
+            if !emitter.emit(Box::new(ProgressMessage(percent))) {
+                return None;
+            }
+            thread::sleep(Duration::from_millis(30));
+        }
+        Some(Box::new(DoneMessage))
+    });
+    command::keyed("download", stream)
+}
+
+impl App for Model {
+    fn init(&self) -> Option<Command> {
+        Some(download())
+    }
+
+    fn update(&mut self, msg: Message) -> Option<Command> {
+        if let Some(key_event) = msg.downcast_ref::<KeyEvent>() {
+            match key_event.code {
+                KeyCode::Char('c') if key_event.modifiers == KeyModifiers::CONTROL => {
+                    return Some(Box::new(command::quit));
+                }
+                KeyCode::Char('r') => {
+                    self.percent = 0;
+                    self.done = false;
+                    return Some(download());
+                }
+                _ => (),
+            }
+        } else if let Some(progress) = msg.downcast_ref::<ProgressMessage>() {
+            self.percent = progress.0;
+        } else if msg.is::<DoneMessage>() {
+            self.done = true;
+        }
+
+        None
+    }
+
+    fn view(&self) -> String {
+        let filled = (self.percent / 5) as usize;
+        let bar = format!(
+            "[{}{}] {}%",
+            "#".repeat(filled),
+            "-".repeat(20 - filled),
+            self.percent
+        );
+        let status = if self.done { "Done!" } else { "Downloading..." };
+        format!(
+            "{}\n{}\n\nPress r to restart, or ctrl+c to quit.",
+            status, bar
+        )
+    }
+}
+
+fn main() {
+    rustea::run(Model {
+        percent: 0,
+        done: false,
+    })
+    .unwrap();
+}
@@ -0,0 +1,57 @@
+use std::{
+    net::TcpListener,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+};
+
+use rustea::crossterm::event::{KeyCode, KeyEvent};
+use rustea::{command, serve, App, Command, Message, Program, ResizeEvent};
+
+// Connect with `telnet localhost 2323`, from as many terminals as you like.
+struct Session {
+    number: usize,
+    size: Option<(u16, u16)>,
+    presses: u32,
+}
+
+impl App for Session {
+    fn update(&mut self, msg: Message) -> Option<Command> {
+        if let Some(resize_event) = msg.downcast_ref::<ResizeEvent>() {
+            self.size = Some((resize_event.0, resize_event.1));
+        } else if let Some(key_event) = msg.downcast_ref::<KeyEvent>() {
+            if key_event.code == KeyCode::Char('q') {
+                return Some(Box::new(command::quit));
+            }
+            self.presses += 1;
+        }
+
+        None
+    }
+
+    fn view(&self) -> String {
+        let size = match self.size {
+            Some((x, y)) => format!("{}x{}", x, y),
+            None => "unknown".to_string(),
+        };
+        format!(
+            "You are session #{}\nYour terminal is {}\nYou pressed {} keys\n\nPress q to disconnect",
+            self.number, size, self.presses
+        )
+    }
+}
+
+fn main() {
+    let listener = TcpListener::bind("127.0.0.1:2323").unwrap();
+    let sessions = Arc::new(AtomicUsize::new(0));
+
+    serve::run(listener, move || {
+        Program::new(Session {
+            number: sessions.fetch_add(1, Ordering::Relaxed) + 1,
+            size: None,
+            presses: 0,
+        })
+    })
+    .unwrap();
+}
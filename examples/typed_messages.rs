@@ -0,0 +1,52 @@
+use rustea::{
+    crossterm::event::{KeyCode, KeyModifiers},
+    Event, TypedApp, TypedCommand,
+};
+
+enum Msg {
+    Increment,
+    Decrement,
+    Quit,
+    Ignored,
+}
+
+impl From<Event> for Msg {
+    fn from(event: Event) -> Self {
+        match event {
+            Event::Key(key_event) => match key_event.code {
+                KeyCode::Char('c') if key_event.modifiers == KeyModifiers::CONTROL => Msg::Quit,
+                KeyCode::Up => Msg::Increment,
+                KeyCode::Down => Msg::Decrement,
+                _ => Msg::Ignored,
+            },
+            _ => Msg::Ignored,
+        }
+    }
+}
+
+struct Model {
+    count: i64,
+}
+
+impl TypedApp for Model {
+    type Msg = Msg;
+
+    fn update(&mut self, msg: Msg) -> Option<TypedCommand<Msg>> {
+        match msg {
+            Msg::Increment => self.count += 1,
+            Msg::Decrement => self.count -= 1,
+            Msg::Quit => return Some(TypedCommand::quit()),
+            Msg::Ignored => (),
+        }
+
+        None
+    }
+
+    fn view(&self) -> String {
+        format!("Count: {}\nUse the arrow keys to change it.", self.count)
+    }
+}
+
+fn main() {
+    rustea::run(Model { count: 0 }).unwrap();
+}
@@ -1,10 +1,49 @@
-use crate::{Command, Message};
+use std::{
+    any::Any,
+    collections::BTreeSet,
+    fmt, io,
+    marker::PhantomData,
+    process::{self, ExitStatus},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Condvar, Mutex, MutexGuard,
+    },
+    time::{Duration, Instant},
+};
+#[cfg(feature = "tokio")]
+use std::{future::Future, pin::Pin};
 
-pub(crate) struct QuitMessage;
+use crate::{Command, Emitter, Message};
 
-/// A built in command that quits the application.
+pub(crate) struct QuitMessage(pub i32);
+
+/// A built in command that quits the application, with an exit code of 0.
 pub fn quit() -> Option<Message> {
-    Some(Box::new(QuitMessage))
+    Some(Box::new(QuitMessage(0)))
+}
+
+/// A built in command that quits the application with the given exit code.
+///
+/// The code is returned from `Program::run` in `Exit::code`, once the terminal has been restored,
+/// so it can be passed on to `std::process::exit`.
+pub fn quit_with(code: i32) -> Command {
+    Box::new(move || Some(Box::new(QuitMessage(code))))
+}
+
+pub(crate) struct SuspendProcessMessage;
+
+/// A built in command that suspends the program, like Ctrl-Z does in a shell.
+///
+/// Since the terminal is in raw mode, pressing Ctrl-Z arrives as a key event instead of suspending the program,
+/// so applications that want to support it should return this command when it is pressed.
+///
+/// The application receives a `SuspendMessage` first. Then the terminal is restored and the process is stopped,
+/// until it is continued, for example with `fg`. Once it is, the terminal is set up again,
+/// the frame is redrawn from scratch, and the application receives a `ResumeMessage`.
+///
+/// Only supported on unix. Elsewhere, this does nothing.
+pub fn suspend() -> Option<Message> {
+    Some(Box::new(SuspendProcessMessage))
 }
 
 pub(crate) struct BatchMessage(pub Vec<Command>);
@@ -15,3 +54,516 @@ pub(crate) struct BatchMessage(pub Vec<Command>);
 pub fn batch(cmds: Vec<Command>) -> Command {
     Box::new(|| Some(Box::new(BatchMessage(cmds))))
 }
+
+pub(crate) struct SequenceMessage(pub Vec<Command>);
+
+/// A built in command that executes the given commands one after another.
+///
+/// Each command is only executed once the previous one has finished, and its message has been handled by `update`.
+/// If handling that message leads to more commands, like a `batch` or another `sequence`,
+/// those are waited for too. So sequences and batches can be nested freely.
+///
+/// # Example
+///
+/// ```
+/// # use rustea::{command, Command};
+/// # fn save_file() -> Command { Box::new(|| None) }
+/// # fn reload() -> Command { Box::new(|| None) }
+/// let cmd = command::sequence(vec![save_file(), reload(), Box::new(command::quit)]);
+/// ```
+pub fn sequence(cmds: Vec<Command>) -> Command {
+    Box::new(|| Some(Box::new(SequenceMessage(cmds))))
+}
+
+pub(crate) struct SpawnMessage(pub Command);
+
+/// A built in command that executes the given command on a dedicated thread, instead of on one of the workers.
+///
+/// Use this for commands that block for a long time, so they don't keep other commands waiting.
+pub fn spawn(cmd: Command) -> Command {
+    Box::new(|| Some(Box::new(SpawnMessage(cmd))))
+}
+
+/// Tells a command whether its result is still wanted.
+///
+/// Long running commands should check it every now and then, and give up early once it is cancelled.
+/// Tokens are handed out by `cancellable`.
+#[derive(Debug, Clone, Default)]
+pub struct CancelToken(Arc<CancelState>);
+
+#[derive(Debug, Default)]
+struct CancelState {
+    cancelled: Mutex<bool>,
+    changed: Condvar,
+}
+
+impl CancelToken {
+    /// Whether the command was cancelled.
+    pub fn is_cancelled(&self) -> bool {
+        *self.lock()
+    }
+
+    /// Blocks for the given duration, or until the token is cancelled, whichever comes first.
+    /// Returns whether it was cancelled.
+    ///
+    /// Use this instead of `thread::sleep`, so that waiting doesn't delay noticing the cancellation.
+    pub fn wait_timeout(&self, timeout: Duration) -> bool {
+        // `None` when the timeout is too long to be represented, which is as good as waiting forever
+        let deadline = Instant::now().checked_add(timeout);
+        let mut cancelled = self.lock();
+        while !*cancelled {
+            cancelled = match deadline {
+                Some(deadline) => {
+                    let now = Instant::now();
+                    if now >= deadline {
+                        break;
+                    }
+                    self.0
+                        .changed
+                        .wait_timeout(cancelled, deadline - now)
+                        .unwrap_or_else(|e| e.into_inner())
+                        .0
+                }
+                None => self
+                    .0
+                    .changed
+                    .wait(cancelled)
+                    .unwrap_or_else(|e| e.into_inner()),
+            };
+        }
+        *cancelled
+    }
+
+    pub(crate) fn cancel(&self) {
+        *self.lock() = true;
+        self.0.changed.notify_all();
+    }
+
+    fn lock(&self) -> MutexGuard<'_, bool> {
+        self.0.cancelled.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+type CancellableFn = Box<dyn FnOnce(CancelToken) -> Option<Message> + Send + 'static>;
+
+pub(crate) struct CancellableMessage(pub CancellableFn);
+
+/// A built in command that passes a `CancelToken` to the given function or closure.
+///
+/// The token is cancelled when the program quits.
+/// When the command is part of a `keyed` command, it is also cancelled when a newer command with the same key
+/// supersedes it, or when the key is cancelled with `cancel`.
+pub fn cancellable(f: impl FnOnce(CancelToken) -> Option<Message> + Send + 'static) -> Command {
+    let f: CancellableFn = Box::new(f);
+    Box::new(|| Some(Box::new(CancellableMessage(f))))
+}
+
+type StreamFn = Box<dyn FnOnce(Emitter) -> Option<Message> + Send + 'static>;
+
+pub(crate) struct StreamMessage(pub StreamFn);
+
+/// A built in command that can send any number of messages while it runs, through the given `Emitter`,
+/// instead of only a single one once it's done. Useful for reporting progress, or tailing a log.
+///
+/// Emitted messages are treated just like the one the command returns: when the command is `keyed`,
+/// they stop reaching `update` once it is superseded or cancelled, and the emitter stops too.
+/// A `sequence` only moves on once the command has returned, and its emitter has been dropped.
+///
+/// Like other commands, it runs on one of the workers. Streams that run for a long time are better off `spawn`ed.
+///
+/// # Example
+///
+/// ```
+/// # use rustea::{command, Command};
+/// struct Progress(u32);
+/// struct Done;
+///
+/// let cmd: Command = command::stream(|emitter| {
+///     for percent in 0..100 {
+///         if !emitter.emit(Box::new(Progress(percent))) {
+///             return None;
+///         }
+///         // download some more
+///     }
+///     Some(Box::new(Done))
+/// });
+/// ```
+pub fn stream(f: impl FnOnce(Emitter) -> Option<Message> + Send + 'static) -> Command {
+    let f: StreamFn = Box::new(f);
+    Box::new(|| Some(Box::new(StreamMessage(f))))
+}
+
+/// The generation of the latest `keyed` or `cancel` command created, in any program.
+static GENERATION: AtomicU64 = AtomicU64::new(0);
+
+/// The keys and generations of the keyed commands that were created, but haven't reached a program yet.
+static PENDING: Mutex<BTreeSet<(String, u64)>> = Mutex::new(BTreeSet::new());
+
+/// Numbers keyed and cancel commands in the order they were created, which is the order
+/// they take effect in, even when the worker pool gets to them in a different one.
+fn next_generation() -> u64 {
+    GENERATION.fetch_add(1, Ordering::Relaxed) + 1
+}
+
+fn pending() -> MutexGuard<'static, BTreeSet<(String, u64)>> {
+    PENDING.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+/// Whether a keyed command with the given key, created before the given generation, is yet to reach a program.
+pub(crate) fn is_pending_before(key: &str, generation: u64) -> bool {
+    pending()
+        .range((key.to_string(), 0)..(key.to_string(), generation))
+        .next()
+        .is_some()
+}
+
+/// A keyed command on its way to a program, until dropped.
+pub(crate) struct PendingKey {
+    pub key: String,
+    pub generation: u64,
+}
+
+impl PendingKey {
+    fn new(key: String) -> Self {
+        let generation = next_generation();
+        pending().insert((key.clone(), generation));
+        Self { key, generation }
+    }
+}
+
+impl Drop for PendingKey {
+    fn drop(&mut self) {
+        pending().remove(&(std::mem::take(&mut self.key), self.generation));
+    }
+}
+
+pub(crate) struct KeyedMessage {
+    pub pending: PendingKey,
+    pub cmd: Command,
+}
+
+/// A built in command that executes the given command under a key.
+///
+/// Executing another keyed command with the same key, that was created after this one, supersedes this one:
+/// any message this one produces afterwards never reaches `update`,
+/// and if it is `cancellable`, its token is cancelled. If it hasn't started yet, it never does.
+/// This keeps results of outdated work, like a search for what the user typed a few keystrokes ago,
+/// from overwriting newer ones.
+///
+/// # Example
+///
+/// ```
+/// # use rustea::{command, Command, Message};
+/// # fn search(query: &str) -> Vec<String> { vec![query.to_string()] }
+/// struct SearchResults(Vec<String>);
+///
+/// fn search_command(query: String) -> Command {
+///     command::keyed(
+///         "search",
+///         command::cancellable(move |token| {
+///             let results = search(&query);
+///             if token.is_cancelled() {
+///                 return None;
+///             }
+///             Some(Box::new(SearchResults(results)))
+///         }),
+///     )
+/// }
+/// ```
+pub fn keyed(key: impl Into<String>, cmd: Command) -> Command {
+    let pending = PendingKey::new(key.into());
+    Box::new(|| Some(Box::new(KeyedMessage { pending, cmd })))
+}
+
+pub(crate) struct CancelMessage {
+    pub key: String,
+    pub generation: u64,
+}
+
+/// A built in command that cancels the `keyed` command with the given key that was created last before it.
+///
+/// Any message it produces afterwards never reaches `update`, and if it is `cancellable`, its token is cancelled.
+pub fn cancel(key: impl Into<String>) -> Command {
+    let key = key.into();
+    let generation = next_generation();
+    Box::new(move || Some(Box::new(CancelMessage { key, generation })))
+}
+
+pub(crate) enum Delay {
+    After(Duration),
+    Boundary(Duration),
+}
+
+pub(crate) struct TimerMessage {
+    pub delay: Delay,
+    pub fire: Box<dyn FnOnce(Instant) -> Message + Send + 'static>,
+}
+
+/// A built in command that dispatches the message made by `f` once the given duration has passed.
+/// `f` receives the instant the timer fired.
+///
+/// All timers are driven by a single thread, so waiting doesn't take up a worker.
+///
+/// # Example
+///
+/// ```
+/// # use std::time::{Duration, Instant};
+/// # use rustea::{command, Command};
+/// struct TimeoutMessage(Instant);
+///
+/// let cmd: Command = command::tick(Duration::from_secs(5), |at| Box::new(TimeoutMessage(at)));
+/// ```
+pub fn tick(duration: Duration, f: impl FnOnce(Instant) -> Message + Send + 'static) -> Command {
+    timer(Delay::After(duration), f)
+}
+
+/// A built in command that dispatches the message made by `f` when the system clock reaches the next
+/// multiple of the given duration. With a duration of one second, it fires at the start of the next second.
+///
+/// It fires once. To keep ticking, return another `every` from `update` when its message arrives.
+/// Since every tick lines up with the clock, the ticks don't drift, no matter how long `update` took.
+pub fn every(duration: Duration, f: impl FnOnce(Instant) -> Message + Send + 'static) -> Command {
+    timer(Delay::Boundary(duration), f)
+}
+
+fn timer(delay: Delay, f: impl FnOnce(Instant) -> Message + Send + 'static) -> Command {
+    let fire = Box::new(f);
+    Box::new(|| Some(Box::new(TimerMessage { delay, fire })))
+}
+
+pub(crate) struct PrintMessage(pub String);
+
+/// A built in command that prints a line above the frame of an inline program, where it stays for good.
+/// The frame keeps being drawn below it.
+///
+/// Useful for logging progress, like finished downloads, while the app keeps running.
+/// Programs that aren't inline have no room above their frame, so nothing is printed.
+pub fn println(text: impl Into<String>) -> Command {
+    let text = text.into();
+    Box::new(|| Some(Box::new(PrintMessage(text))))
+}
+
+/// Like `println`, but formats its arguments first.
+///
+/// # Example
+///
+/// ```
+/// # use rustea::command;
+/// # let file = "foo.tar.gz";
+/// let cmd = command::printf(format_args!("✓ downloaded {}", file));
+/// ```
+pub fn printf(args: fmt::Arguments) -> Command {
+    println(args.to_string())
+}
+
+/// Delivered once the process started by `exec` has exited.
+#[derive(Debug)]
+pub struct ExecFinished {
+    /// How the process exited, or why it couldn't be started.
+    pub status: io::Result<ExitStatus>,
+}
+
+pub(crate) type ExecDone = Box<dyn FnOnce(ExecFinished) -> Message + Send + 'static>;
+
+pub(crate) struct ExecMessage {
+    pub cmd: process::Command,
+    pub done: ExecDone,
+}
+
+/// A built in command that hands the terminal over to a process, like an editor, and waits for it to exit.
+///
+/// The terminal is restored first, and the process inherits stdin, stdout and stderr, unless they were configured.
+/// No events are read while it runs, and interrupts from the keyboard, like Ctrl-C, are left to the process
+/// instead of being delivered as `SignalMessage`s. Once it exits, the terminal is set up again,
+/// the frame is redrawn from scratch, and an `ExecFinished` message is delivered.
+///
+/// # Example
+///
+/// ```
+/// # use rustea::{command, Command};
+/// let editor = std::env::var("EDITOR").unwrap_or_else(|_| "vi".to_string());
+/// let mut process = std::process::Command::new(editor);
+/// process.arg("notes.txt");
+///
+/// let cmd: Command = command::exec(process);
+/// ```
+pub fn exec(cmd: process::Command) -> Command {
+    exec_then(cmd, |finished| Box::new(finished))
+}
+
+fn exec_then(
+    cmd: process::Command,
+    done: impl FnOnce(ExecFinished) -> Message + Send + 'static,
+) -> Command {
+    let done = Box::new(done);
+    Box::new(|| Some(Box::new(ExecMessage { cmd, done })))
+}
+
+#[cfg(feature = "tokio")]
+pub(crate) struct FutureMessage(
+    pub Pin<Box<dyn Future<Output = Option<Message>> + Send + 'static>>,
+);
+
+/// A built in command that runs a future on the program's tokio runtime, and dispatches the message it resolves to.
+///
+/// This lets commands use async libraries, instead of blocking a worker thread.
+/// The runtime is either the one given to `Program::tokio_handle`,
+/// or one the program creates the first time a future is run.
+///
+/// Only available with the `tokio` feature.
+///
+/// # Example
+///
+/// ```no_run
+/// # use rustea::{command, Command};
+/// struct HttpResponse(String);
+///
+/// fn make_request_command(url: String) -> Command {
+///     command::future(async move {
+///         let text_response = reqwest::get(url).await.ok()?.text().await.ok()?;
+///         Some(Box::new(HttpResponse(text_response)) as rustea::Message)
+///     })
+/// }
+/// ```
+#[cfg(feature = "tokio")]
+pub fn future(fut: impl Future<Output = Option<Message>> + Send + 'static) -> Command {
+    let fut = Box::pin(fut);
+    Box::new(|| Some(Box::new(FutureMessage(fut))))
+}
+
+/// A command that can only produce messages of type `M`.
+///
+/// These are the commands of apps implementing `TypedApp`.
+/// The built in commands, and any other plain `Command`, can be turned into one with `from_untyped`.
+pub struct TypedCommand<M> {
+    cmd: Command,
+    msg: PhantomData<fn() -> M>,
+}
+
+impl<M: Send + 'static> TypedCommand<M> {
+    /// Wraps a function or closure that optionally produces a message.
+    /// Just like a `Command`, it is processed on a worker thread.
+    pub fn new(f: impl FnOnce() -> Option<M> + Send + 'static) -> Self {
+        Self::from_untyped(Box::new(move || f().map(erase)))
+    }
+
+    /// The typed version of `sequence`.
+    pub fn sequence(cmds: Vec<TypedCommand<M>>) -> Self {
+        Self::from_untyped(sequence(cmds.into_iter().map(Self::into_untyped).collect()))
+    }
+
+    /// The typed version of `spawn`.
+    pub fn spawn(cmd: TypedCommand<M>) -> Self {
+        Self::from_untyped(spawn(cmd.into_untyped()))
+    }
+
+    /// The typed version of `cancellable`.
+    pub fn cancellable(f: impl FnOnce(CancelToken) -> Option<M> + Send + 'static) -> Self {
+        Self::from_untyped(cancellable(move |token| f(token).map(erase)))
+    }
+
+    /// The typed version of `stream`, whose emitter sends `M` values.
+    pub fn stream(f: impl FnOnce(Emitter<M>) -> Option<M> + Send + 'static) -> Self {
+        Self::from_untyped(stream(move |emitter| f(emitter.retype()).map(erase)))
+    }
+
+    /// The typed version of `keyed`.
+    pub fn keyed(key: impl Into<String>, cmd: TypedCommand<M>) -> Self {
+        Self::from_untyped(keyed(key, cmd.into_untyped()))
+    }
+
+    /// The typed version of `cancel`.
+    pub fn cancel(key: impl Into<String>) -> Self {
+        Self::from_untyped(cancel(key))
+    }
+
+    /// The typed version of `tick`.
+    pub fn tick(duration: Duration, f: impl FnOnce(Instant) -> M + Send + 'static) -> Self {
+        Self::from_untyped(tick(duration, move |at| erase(f(at))))
+    }
+
+    /// The typed version of `every`.
+    pub fn every(duration: Duration, f: impl FnOnce(Instant) -> M + Send + 'static) -> Self {
+        Self::from_untyped(every(duration, move |at| erase(f(at))))
+    }
+
+    /// The typed version of `println`.
+    pub fn println(text: impl Into<String>) -> Self {
+        Self::from_untyped(println(text))
+    }
+
+    /// The typed version of `printf`.
+    pub fn printf(args: fmt::Arguments) -> Self {
+        Self::from_untyped(printf(args))
+    }
+
+    /// The typed version of `exec`. `f` turns the `ExecFinished` into the app's message.
+    pub fn exec(cmd: process::Command, f: impl FnOnce(ExecFinished) -> M + Send + 'static) -> Self {
+        Self::from_untyped(exec_then(cmd, move |finished| erase(f(finished))))
+    }
+
+    /// The typed version of `future`.
+    ///
+    /// Only available with the `tokio` feature.
+    #[cfg(feature = "tokio")]
+    pub fn future(fut: impl Future<Output = Option<M>> + Send + 'static) -> Self {
+        Self::from_untyped(future(async move { fut.await.map(erase) }))
+    }
+
+    /// Wraps a plain `Command`, such as one of the built in commands.
+    ///
+    /// Any message it produces that isn't an `M` never reaches `update`.
+    pub fn from_untyped(cmd: Command) -> Self {
+        Self {
+            cmd,
+            msg: PhantomData,
+        }
+    }
+
+    /// The typed version of `quit`.
+    pub fn quit() -> Self {
+        Self::from_untyped(Box::new(quit))
+    }
+
+    /// The typed version of `quit_with`.
+    pub fn quit_with(code: i32) -> Self {
+        Self::from_untyped(quit_with(code))
+    }
+
+    /// The typed version of `suspend`.
+    pub fn suspend() -> Self {
+        Self::from_untyped(Box::new(suspend))
+    }
+
+    /// The typed version of `batch`.
+    pub fn batch(cmds: Vec<TypedCommand<M>>) -> Self {
+        Self::from_untyped(batch(cmds.into_iter().map(Self::into_untyped).collect()))
+    }
+
+    /// Unwraps the plain `Command`.
+    pub fn into_untyped(self) -> Command {
+        self.cmd
+    }
+}
+
+/// Boxes a message of any type as a `Message`, without boxing it twice if it already is one.
+pub(crate) fn erase<M: Send + 'static>(msg: M) -> Message {
+    let msg: Message = Box::new(msg);
+    match msg.downcast::<Message>() {
+        Ok(msg) => *msg,
+        Err(msg) => msg,
+    }
+}
+
+/// The inverse of `erase`. Gives the message back if it isn't an `M`.
+pub(crate) fn restore<M: 'static>(msg: Message) -> Result<M, Message> {
+    let boxed: Box<dyn Any> = Box::new(msg);
+    match boxed.downcast::<M>() {
+        // `M` is `Message` itself
+        Ok(msg) => Ok(*msg),
+        Err(boxed) => {
+            let msg = *boxed.downcast::<Message>().unwrap();
+            msg.downcast::<M>().map(|msg| *msg)
+        }
+    }
+}
@@ -0,0 +1,43 @@
+use std::{fmt, io};
+
+/// Everything that can go wrong while running a program.
+#[derive(Debug)]
+pub enum Error {
+    /// Writing to, or configuring the terminal failed.
+    Io(io::Error),
+    /// Reading events from the terminal failed.
+    EventRead(io::Error),
+    /// One of the channels the runtime uses internally was closed unexpectedly.
+    ChannelClosed,
+    /// A command or subscription panicked. Contains the panic message, if it had one.
+    CommandPanicked(String),
+}
+
+/// A `Result` with `rustea`'s `Error` as the error type.
+pub type Result<T> = std::result::Result<T, Error>;
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Io(err) => write!(f, "terminal I/O failed: {}", err),
+            Error::EventRead(err) => write!(f, "reading terminal events failed: {}", err),
+            Error::ChannelClosed => write!(f, "an internal channel was closed"),
+            Error::CommandPanicked(msg) => write!(f, "a command panicked: {}", msg),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Io(err) | Error::EventRead(err) => Some(err),
+            Error::ChannelClosed | Error::CommandPanicked(_) => None,
+        }
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(err: io::Error) -> Self {
+        Error::Io(err)
+    }
+}
@@ -0,0 +1,689 @@
+//! Where a program's events come from.
+//!
+//! By default, a `Program` reads events from the terminal it runs in, through `crossterm`.
+//! Any other `EventSource` can be given to `Program::input` instead, which together with `Program::output`
+//! allows running an app over a pty, a socket, or anything else that speaks the terminal's protocol.
+//! `ByteInput` turns any byte stream into events, by parsing the escape sequences terminals send.
+//!
+//! # Example
+//!
+//! ```no_run
+//! # use rustea::{App, Command, Message};
+//! # struct Model;
+//! # impl App for Model {
+//! #     fn update(&mut self, _msg: Message) -> Option<Command> { None }
+//! #     fn view(&self) -> String { String::new() }
+//! # }
+//! use std::net::TcpListener;
+//!
+//! use rustea::{input::ByteInput, Program};
+//!
+//! let listener = TcpListener::bind("127.0.0.1:2323").unwrap();
+//! let (stream, _) = listener.accept().unwrap();
+//!
+//! Program::new(Model)
+//!     .input(ByteInput::new(stream.try_clone().unwrap()))
+//!     .output(stream)
+//!     .run()
+//!     .unwrap();
+//! ```
+
+use std::{
+    io::{self, ErrorKind, Read},
+    str,
+    sync::mpsc::{self, Receiver, RecvTimeoutError},
+    thread,
+    time::Duration,
+};
+
+use crossterm::event::{
+    poll, read, Event as CrosstermEvent, KeyCode, KeyEvent, KeyModifiers, MouseButton, MouseEvent,
+    MouseEventKind,
+};
+
+use crate::Event;
+
+/// A source of key presses, mouse events and resizes for a program.
+///
+/// Events are read on a thread of their own, so reading may block, but only for as long as the timeout.
+/// In between reads, the program checks whether it should stop reading, like when it quits,
+/// or hands the terminal to a child process with `command::exec`.
+pub trait EventSource: Send {
+    /// Waits up to `timeout` for the next event. Returns `None` if none arrived in time.
+    ///
+    /// Returning an error ends the program with `Error::EventRead`.
+    /// Sources whose input has ended should return an error of kind `UnexpectedEof`.
+    fn read(&mut self, timeout: Duration) -> io::Result<Option<Event>>;
+
+    /// The size of the terminal the events come from, if it is known. Defaults to `None`.
+    ///
+    /// Sources that learn about the size later can report it as an `Event::Resize`.
+    fn size(&self) -> Option<(u16, u16)> {
+        None
+    }
+}
+
+/// Reads events from the terminal the process runs in, through `crossterm`. This is the default source.
+#[derive(Debug, Default)]
+pub struct TerminalInput;
+
+impl EventSource for TerminalInput {
+    fn read(&mut self, timeout: Duration) -> io::Result<Option<Event>> {
+        if !poll(timeout)? {
+            return Ok(None);
+        }
+
+        Ok(Some(match read()? {
+            CrosstermEvent::Key(event) => Event::Key(event),
+            CrosstermEvent::Mouse(event) => Event::Mouse(event),
+            CrosstermEvent::Resize(x, y) => Event::Resize(x, y),
+        }))
+    }
+
+    fn size(&self) -> Option<(u16, u16)> {
+        crossterm::terminal::size().ok()
+    }
+}
+
+/// Reads events from a byte stream, like a socket or the master side of a pty,
+/// by parsing the escape sequences a terminal sends for keys and the mouse.
+///
+/// The stream is read on a thread of its own, which stops once the stream ends or fails,
+/// or once the `ByteInput` has been dropped and the next read returns.
+#[derive(Debug)]
+pub struct ByteInput {
+    chunks: Receiver<io::Result<Vec<u8>>>,
+    parser: Parser,
+    events: std::vec::IntoIter<Event>,
+    size: Option<(u16, u16)>,
+}
+
+impl ByteInput {
+    /// Starts reading from the given stream.
+    pub fn new(reader: impl Read + Send + 'static) -> Self {
+        Self {
+            chunks: read_chunks(reader),
+            parser: Parser::default(),
+            events: Vec::new().into_iter(),
+            size: None,
+        }
+    }
+
+    /// The size of the terminal on the other end of the stream, if it is known some other way.
+    pub fn terminal_size(mut self, width: u16, height: u16) -> Self {
+        self.size = Some((width, height));
+        self
+    }
+}
+
+impl EventSource for ByteInput {
+    fn read(&mut self, timeout: Duration) -> io::Result<Option<Event>> {
+        if let Some(event) = self.events.next() {
+            return Ok(Some(event));
+        }
+
+        match self.chunks.recv_timeout(timeout) {
+            Ok(chunk) => {
+                self.events = self.parser.parse(&chunk?).into_iter();
+                Ok(self.events.next())
+            }
+            Err(RecvTimeoutError::Timeout) => Ok(None),
+            Err(RecvTimeoutError::Disconnected) => Err(ErrorKind::UnexpectedEof.into()),
+        }
+    }
+
+    fn size(&self) -> Option<(u16, u16)> {
+        self.size
+    }
+}
+
+/// Reads the stream on a thread of its own, and sends whatever was read as it arrives.
+/// The channel is closed once the stream ends or fails,
+/// or once the receiver has been dropped and the next read returns.
+pub(crate) fn read_chunks(mut reader: impl Read + Send + 'static) -> Receiver<io::Result<Vec<u8>>> {
+    let (chunk_tx, chunks) = mpsc::channel();
+    thread::spawn(move || {
+        let mut buffer = [0; 1024];
+        loop {
+            let chunk = match reader.read(&mut buffer) {
+                Ok(0) => return,
+                Ok(n) => Ok(buffer[..n].to_vec()),
+                Err(err) if err.kind() == ErrorKind::Interrupted => continue,
+                Err(err) => Err(err),
+            };
+            let failed = chunk.is_err();
+            if chunk_tx.send(chunk).is_err() || failed {
+                return;
+            }
+        }
+    });
+    chunks
+}
+
+/// Turns the bytes a terminal sends into key presses and mouse events.
+///
+/// Terminals send an escape sequence at once, so an escape byte at the end of the bytes given to `parse`
+/// is taken to be the escape key. Other sequences that are cut off are kept until the rest arrives.
+/// Sequences that aren't understood are skipped.
+///
+/// # Example
+///
+/// ```
+/// use rustea::{
+///     crossterm::event::{KeyCode, KeyEvent, KeyModifiers},
+///     input::Parser,
+///     Event,
+/// };
+///
+/// let mut parser = Parser::default();
+/// assert_eq!(
+///     parser.parse(b"a\x1b[A"),
+///     vec![
+///         Event::Key(KeyEvent::new(KeyCode::Char('a'), KeyModifiers::NONE)),
+///         Event::Key(KeyEvent::new(KeyCode::Up, KeyModifiers::NONE)),
+///     ]
+/// );
+/// ```
+#[derive(Debug, Default)]
+pub struct Parser {
+    /// The start of a sequence that was cut off.
+    pending: Vec<u8>,
+}
+
+/// The result of parsing the start of some bytes.
+enum Parsed {
+    /// A sequence of the given length, which may or may not have been understood.
+    Sequence(Option<Event>, usize),
+    /// The start of a sequence, which needs more bytes.
+    Incomplete,
+}
+
+impl Parser {
+    /// Parses the given bytes, along with whatever was left over from before.
+    pub fn parse(&mut self, bytes: &[u8]) -> Vec<Event> {
+        self.pending.extend_from_slice(bytes);
+
+        let mut events = Vec::new();
+        let mut start = 0;
+        while start < self.pending.len() {
+            match parse_event(&self.pending[start..]) {
+                Parsed::Sequence(event, len) => {
+                    events.extend(event);
+                    start += len;
+                }
+                Parsed::Incomplete => break,
+            }
+        }
+        self.pending.drain(..start);
+        events
+    }
+}
+
+fn parse_event(bytes: &[u8]) -> Parsed {
+    let key = |code, modifiers, len| Parsed::Sequence(Some(key_event(code, modifiers)), len);
+    match bytes[0] {
+        b'\x1b' => match bytes.get(1) {
+            None => key(KeyCode::Esc, KeyModifiers::NONE, 1),
+            Some(b'[') => parse_csi(bytes),
+            Some(b'O') => parse_ss3(bytes),
+            Some(b'\x1b') => key(KeyCode::Esc, KeyModifiers::NONE, 1),
+            // anything else following an escape was typed with alt held down
+            Some(_) => match parse_event(&bytes[1..]) {
+                Parsed::Sequence(Some(Event::Key(event)), len) => {
+                    key(event.code, event.modifiers | KeyModifiers::ALT, len + 1)
+                }
+                Parsed::Sequence(_, len) => Parsed::Sequence(None, len + 1),
+                Parsed::Incomplete => Parsed::Incomplete,
+            },
+        },
+        // some streams, like telnet, follow a carriage return with a line feed or a null byte
+        b'\r' => match bytes.get(1) {
+            Some(b'\n' | b'\0') => key(KeyCode::Enter, KeyModifiers::NONE, 2),
+            _ => key(KeyCode::Enter, KeyModifiers::NONE, 1),
+        },
+        b'\n' => key(KeyCode::Enter, KeyModifiers::NONE, 1),
+        b'\t' => key(KeyCode::Tab, KeyModifiers::NONE, 1),
+        b'\x7f' => key(KeyCode::Backspace, KeyModifiers::NONE, 1),
+        c @ b'\x01'..=b'\x1a' => key(
+            KeyCode::Char(char::from(c - 0x01 + b'a')),
+            KeyModifiers::CONTROL,
+            1,
+        ),
+        c @ b'\x1c'..=b'\x1f' => key(
+            KeyCode::Char(char::from(c - 0x1c + b'4')),
+            KeyModifiers::CONTROL,
+            1,
+        ),
+        b'\0' => key(KeyCode::Char(' '), KeyModifiers::CONTROL, 1),
+        _ => parse_char(bytes),
+    }
+}
+
+fn parse_char(bytes: &[u8]) -> Parsed {
+    let len = match bytes[0] {
+        0x00..=0x7f => 1,
+        0xc0..=0xdf => 2,
+        0xe0..=0xef => 3,
+        0xf0..=0xf7 => 4,
+        _ => return Parsed::Sequence(None, 1),
+    };
+    if bytes.len() < len {
+        return Parsed::Incomplete;
+    }
+
+    match str::from_utf8(&bytes[..len])
+        .ok()
+        .and_then(|s| s.chars().next())
+    {
+        Some(c) => {
+            let modifiers = if c.is_uppercase() {
+                KeyModifiers::SHIFT
+            } else {
+                KeyModifiers::NONE
+            };
+            Parsed::Sequence(Some(key_event(KeyCode::Char(c), modifiers)), len)
+        }
+        None => Parsed::Sequence(None, 1),
+    }
+}
+
+/// Parses `ESC O`, which some terminals send for the arrow keys and F1 to F4.
+fn parse_ss3(bytes: &[u8]) -> Parsed {
+    let code = match bytes.get(2) {
+        None => return Parsed::Incomplete,
+        Some(b'A') => KeyCode::Up,
+        Some(b'B') => KeyCode::Down,
+        Some(b'C') => KeyCode::Right,
+        Some(b'D') => KeyCode::Left,
+        Some(b'H') => KeyCode::Home,
+        Some(b'F') => KeyCode::End,
+        Some(&c @ b'P'..=b'S') => KeyCode::F(1 + c - b'P'),
+        Some(_) => return Parsed::Sequence(None, 3),
+    };
+    Parsed::Sequence(Some(key_event(code, KeyModifiers::NONE)), 3)
+}
+
+/// Parses `ESC [`, followed by parameters and a final byte.
+fn parse_csi(bytes: &[u8]) -> Parsed {
+    match bytes.get(2) {
+        None => return Parsed::Incomplete,
+        Some(b'M') => return parse_x10_mouse(bytes),
+        _ => (),
+    }
+
+    // parameters and intermediate bytes are in 0x20..=0x3f, the final byte in 0x40..=0x7e
+    let end = match bytes[2..].iter().position(|b| !(0x20..=0x3f).contains(b)) {
+        Some(i) => i + 2,
+        None => return Parsed::Incomplete,
+    };
+    let len = end + 1;
+    let params = match str::from_utf8(&bytes[2..end]) {
+        Ok(params) => params,
+        Err(_) => return Parsed::Sequence(None, len),
+    };
+    let final_byte = bytes[end];
+    if !(0x40..=0x7e).contains(&final_byte) {
+        // cut off by something that can't be part of a sequence, which is parsed on its own
+        return Parsed::Sequence(None, end);
+    }
+
+    if let Some(params) = params.strip_prefix('<') {
+        return Parsed::Sequence(parse_sgr_mouse(params, final_byte), len);
+    }
+
+    let numbers: Vec<u16> = params.split(';').map(|n| n.parse().unwrap_or(1)).collect();
+    let modifiers = numbers
+        .get(1)
+        .map_or(KeyModifiers::NONE, |&mask| parse_modifiers(mask));
+    let code = match final_byte {
+        b'A' => KeyCode::Up,
+        b'B' => KeyCode::Down,
+        b'C' => KeyCode::Right,
+        b'D' => KeyCode::Left,
+        b'H' => KeyCode::Home,
+        b'F' => KeyCode::End,
+        b'P'..=b'S' => KeyCode::F(1 + final_byte - b'P'),
+        b'Z' => {
+            return Parsed::Sequence(Some(key_event(KeyCode::BackTab, KeyModifiers::SHIFT)), len)
+        }
+        b'~' => match numbers[0] {
+            1 | 7 => KeyCode::Home,
+            2 => KeyCode::Insert,
+            3 => KeyCode::Delete,
+            4 | 8 => KeyCode::End,
+            5 => KeyCode::PageUp,
+            6 => KeyCode::PageDown,
+            n @ 11..=15 => KeyCode::F((n - 10) as u8),
+            n @ 17..=21 => KeyCode::F((n - 11) as u8),
+            n @ 23..=24 => KeyCode::F((n - 12) as u8),
+            _ => return Parsed::Sequence(None, len),
+        },
+        _ => return Parsed::Sequence(None, len),
+    };
+    Parsed::Sequence(Some(key_event(code, modifiers)), len)
+}
+
+/// Parses `ESC [ < button ; column ; row`, followed by `M` for a press, or `m` for a release.
+fn parse_sgr_mouse(params: &str, final_byte: u8) -> Option<Event> {
+    let mut numbers = params.split(';').map(|n| n.parse::<u16>().ok());
+    let (button, column, row) = (numbers.next()??, numbers.next()??, numbers.next()??);
+    let (mut kind, modifiers) = parse_mouse_button(button)?;
+    if final_byte == b'm' {
+        if let MouseEventKind::Down(button) = kind {
+            kind = MouseEventKind::Up(button);
+        }
+    } else if final_byte != b'M' {
+        return None;
+    }
+    Some(mouse_event(kind, modifiers, column, row))
+}
+
+/// Parses `ESC [ M`, followed by the button, the column and the row, each as a byte offset by 32.
+fn parse_x10_mouse(bytes: &[u8]) -> Parsed {
+    if bytes.len() < 6 {
+        return Parsed::Incomplete;
+    }
+
+    let [button, column, row] =
+        [bytes[3], bytes[4], bytes[5]].map(|b| u16::from(b.saturating_sub(32)));
+    let event = parse_mouse_button(button)
+        .map(|(kind, modifiers)| mouse_event(kind, modifiers, column, row));
+    Parsed::Sequence(event, 6)
+}
+
+fn parse_mouse_button(button: u16) -> Option<(MouseEventKind, KeyModifiers)> {
+    let mut modifiers = KeyModifiers::NONE;
+    if button & 4 != 0 {
+        modifiers |= KeyModifiers::SHIFT;
+    }
+    if button & 8 != 0 {
+        modifiers |= KeyModifiers::ALT;
+    }
+    if button & 16 != 0 {
+        modifiers |= KeyModifiers::CONTROL;
+    }
+
+    let motion = button & 32 != 0;
+    let kind = match (button & 0b1100_0011, motion) {
+        (0, false) => MouseEventKind::Down(MouseButton::Left),
+        (1, false) => MouseEventKind::Down(MouseButton::Middle),
+        (2, false) => MouseEventKind::Down(MouseButton::Right),
+        (0, true) => MouseEventKind::Drag(MouseButton::Left),
+        (1, true) => MouseEventKind::Drag(MouseButton::Middle),
+        (2, true) => MouseEventKind::Drag(MouseButton::Right),
+        // the older encoding doesn't say which button was released
+        (3, false) => MouseEventKind::Up(MouseButton::Left),
+        (3, true) => MouseEventKind::Moved,
+        (64, _) => MouseEventKind::ScrollUp,
+        (65, _) => MouseEventKind::ScrollDown,
+        _ => return None,
+    };
+    Some((kind, modifiers))
+}
+
+/// Parses the modifiers of a key, which are sent as one more than a mask.
+fn parse_modifiers(mask: u16) -> KeyModifiers {
+    let mask = mask.saturating_sub(1);
+    let mut modifiers = KeyModifiers::NONE;
+    if mask & 1 != 0 {
+        modifiers |= KeyModifiers::SHIFT;
+    }
+    if mask & 2 != 0 {
+        modifiers |= KeyModifiers::ALT;
+    }
+    if mask & 4 != 0 {
+        modifiers |= KeyModifiers::CONTROL;
+    }
+    modifiers
+}
+
+fn key_event(code: KeyCode, modifiers: KeyModifiers) -> Event {
+    Event::Key(KeyEvent::new(code, modifiers))
+}
+
+/// Positions are sent counting from 1, but `MouseEvent`s count from 0.
+fn mouse_event(kind: MouseEventKind, modifiers: KeyModifiers, column: u16, row: u16) -> Event {
+    Event::Mouse(MouseEvent {
+        kind,
+        column: column.saturating_sub(1),
+        row: row.saturating_sub(1),
+        modifiers,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn key(code: KeyCode, modifiers: KeyModifiers) -> Event {
+        key_event(code, modifiers)
+    }
+
+    fn mouse(kind: MouseEventKind, modifiers: KeyModifiers, column: u16, row: u16) -> Event {
+        Event::Mouse(MouseEvent {
+            kind,
+            column,
+            row,
+            modifiers,
+        })
+    }
+
+    fn parse(bytes: &[u8]) -> Vec<Event> {
+        Parser::default().parse(bytes)
+    }
+
+    #[test]
+    fn sequences_split_across_reads() {
+        let mut parser = Parser::default();
+        assert_eq!(
+            parser.parse(b"a\x1b["),
+            vec![key(KeyCode::Char('a'), KeyModifiers::NONE)]
+        );
+        assert_eq!(parser.parse(b"1;"), vec![]);
+        assert_eq!(
+            parser.parse(b"5Cb"),
+            vec![
+                key(KeyCode::Right, KeyModifiers::CONTROL),
+                key(KeyCode::Char('b'), KeyModifiers::NONE),
+            ]
+        );
+
+        assert_eq!(parser.parse(b"\x1b[<0;1"), vec![]);
+        assert_eq!(
+            parser.parse(b"0;5M"),
+            vec![mouse(
+                MouseEventKind::Down(MouseButton::Left),
+                KeyModifiers::NONE,
+                9,
+                4
+            )]
+        );
+
+        assert_eq!(parser.parse(b"\x1b[M "), vec![]);
+        assert_eq!(
+            parser.parse(b"!!"),
+            vec![mouse(
+                MouseEventKind::Down(MouseButton::Left),
+                KeyModifiers::NONE,
+                0,
+                0
+            )]
+        );
+
+        assert_eq!(parser.parse(b"\x1bO"), vec![]);
+        assert_eq!(
+            parser.parse(b"P"),
+            vec![key(KeyCode::F(1), KeyModifiers::NONE)]
+        );
+    }
+
+    #[test]
+    fn characters_split_across_reads() {
+        let mut parser = Parser::default();
+        let bytes = "é€".as_bytes();
+        assert_eq!(parser.parse(&bytes[..1]), vec![]);
+        assert_eq!(
+            parser.parse(&bytes[1..3]),
+            vec![key(KeyCode::Char('é'), KeyModifiers::NONE)]
+        );
+        assert_eq!(parser.parse(&bytes[3..4]), vec![]);
+        assert_eq!(
+            parser.parse(&bytes[4..]),
+            vec![key(KeyCode::Char('€'), KeyModifiers::NONE)]
+        );
+        assert_eq!(
+            parser.parse(b"A"),
+            vec![key(KeyCode::Char('A'), KeyModifiers::SHIFT)]
+        );
+    }
+
+    #[test]
+    fn sgr_mouse() {
+        assert_eq!(
+            parse(b"\x1b[<0;10;5M\x1b[<0;10;5m"),
+            vec![
+                mouse(
+                    MouseEventKind::Down(MouseButton::Left),
+                    KeyModifiers::NONE,
+                    9,
+                    4
+                ),
+                mouse(
+                    MouseEventKind::Up(MouseButton::Left),
+                    KeyModifiers::NONE,
+                    9,
+                    4
+                ),
+            ]
+        );
+        assert_eq!(
+            parse(b"\x1b[<34;300;200M\x1b[<35;1;1M"),
+            vec![
+                mouse(
+                    MouseEventKind::Drag(MouseButton::Right),
+                    KeyModifiers::NONE,
+                    299,
+                    199
+                ),
+                mouse(MouseEventKind::Moved, KeyModifiers::NONE, 0, 0),
+            ]
+        );
+        assert_eq!(
+            parse(b"\x1b[<64;2;3M\x1b[<81;2;3M"),
+            vec![
+                mouse(MouseEventKind::ScrollUp, KeyModifiers::NONE, 1, 2),
+                mouse(MouseEventKind::ScrollDown, KeyModifiers::CONTROL, 1, 2),
+            ]
+        );
+    }
+
+    #[test]
+    fn x10_mouse() {
+        assert_eq!(
+            parse(b"\x1b[M!*%\x1b[M#*%\x1b[M`!!"),
+            vec![
+                mouse(
+                    MouseEventKind::Down(MouseButton::Middle),
+                    KeyModifiers::NONE,
+                    9,
+                    4
+                ),
+                mouse(
+                    MouseEventKind::Up(MouseButton::Left),
+                    KeyModifiers::NONE,
+                    9,
+                    4
+                ),
+                mouse(MouseEventKind::ScrollUp, KeyModifiers::NONE, 0, 0),
+            ]
+        );
+        assert_eq!(
+            parse(b"\x1b[M,*%"),
+            vec![mouse(
+                MouseEventKind::Down(MouseButton::Left),
+                KeyModifiers::SHIFT | KeyModifiers::ALT,
+                9,
+                4
+            )]
+        );
+    }
+
+    #[test]
+    fn modifiers() {
+        assert_eq!(
+            parse(b"\x1b[1;2A\x1b[1;3B\x1b[1;5C\x1b[1;8D\x1b[3;5~\x1b[Z"),
+            vec![
+                key(KeyCode::Up, KeyModifiers::SHIFT),
+                key(KeyCode::Down, KeyModifiers::ALT),
+                key(KeyCode::Right, KeyModifiers::CONTROL),
+                key(
+                    KeyCode::Left,
+                    KeyModifiers::SHIFT | KeyModifiers::ALT | KeyModifiers::CONTROL
+                ),
+                key(KeyCode::Delete, KeyModifiers::CONTROL),
+                key(KeyCode::BackTab, KeyModifiers::SHIFT),
+            ]
+        );
+        assert_eq!(
+            parse(b"\x01\x1a\x1c\x00"),
+            vec![
+                key(KeyCode::Char('a'), KeyModifiers::CONTROL),
+                key(KeyCode::Char('z'), KeyModifiers::CONTROL),
+                key(KeyCode::Char('4'), KeyModifiers::CONTROL),
+                key(KeyCode::Char(' '), KeyModifiers::CONTROL),
+            ]
+        );
+    }
+
+    #[test]
+    fn alt() {
+        assert_eq!(
+            parse(b"\x1ba\x1bA\x1b\x01\x1b\x7f"),
+            vec![
+                key(KeyCode::Char('a'), KeyModifiers::ALT),
+                key(KeyCode::Char('A'), KeyModifiers::SHIFT | KeyModifiers::ALT),
+                key(
+                    KeyCode::Char('a'),
+                    KeyModifiers::CONTROL | KeyModifiers::ALT
+                ),
+                key(KeyCode::Backspace, KeyModifiers::ALT),
+            ]
+        );
+        assert_eq!(
+            parse(b"\x1b\x1b[A"),
+            vec![
+                key(KeyCode::Esc, KeyModifiers::NONE),
+                key(KeyCode::Up, KeyModifiers::NONE)
+            ]
+        );
+        assert_eq!(parse(b"\x1b"), vec![key(KeyCode::Esc, KeyModifiers::NONE)]);
+    }
+
+    #[test]
+    fn line_endings() {
+        let enter = key(KeyCode::Enter, KeyModifiers::NONE);
+        assert_eq!(
+            parse(b"\r\0\r\n\r\n"),
+            vec![enter.clone(), enter.clone(), enter.clone()]
+        );
+        assert_eq!(
+            parse(b"\r\ra"),
+            vec![
+                enter.clone(),
+                enter,
+                key(KeyCode::Char('a'), KeyModifiers::NONE)
+            ]
+        );
+    }
+
+    #[test]
+    fn unknown_sequences_are_skipped() {
+        assert_eq!(
+            parse(b"\x1b[99~\x1b[?1;2c\x1bOXa\xffb"),
+            vec![
+                key(KeyCode::Char('a'), KeyModifiers::NONE),
+                key(KeyCode::Char('b'), KeyModifiers::NONE),
+            ]
+        );
+    }
+}
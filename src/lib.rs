@@ -6,15 +6,31 @@
 pub mod view_helper;
 pub extern crate crossterm;
 pub mod command;
+mod error;
+pub mod input;
+mod pool;
+mod program;
+mod reader;
+mod renderer;
+mod runtime;
+mod scheduler;
+pub mod serve;
+#[cfg(unix)]
+mod signal;
+mod subscription;
+mod terminal;
+pub mod testing;
+mod timer;
 
-use std::{
-    any::Any,
-    io::{stdout, Result, Write},
-    sync::mpsc::{self, Sender},
-    thread,
-};
+use std::any::Any;
 
-use crossterm::event::{read, Event};
+use crossterm::event::{KeyEvent, MouseEvent};
+
+pub use command::TypedCommand;
+pub use error::{Error, Result};
+pub use pool::CommandMetrics;
+pub use program::{Exit, MouseMode, Program, ProgramHandle};
+pub use subscription::{Emitter, Subscription, TypedSubscription};
 
 /// Any boxed type that may or may not contain data.
 /// They are fed to your applications `update` method to tell it how and what to update.
@@ -25,43 +41,123 @@ use crossterm::event::{read, Event};
 /// # Example
 ///
 /// ```
+/// # use rustea::Message;
+/// # struct Model { response: Option<String> }
+/// # let mut model = Model { response: None };
 /// // the type of your message
 /// struct HttpResponse(String);
 ///
 /// // the boxed message itself
-/// let http_response_message = Box::new(HttpResponse("Hello World".to_string()));
+/// let http_response_message: Message = Box::new(HttpResponse("Hello World".to_string()));
 ///
 /// // determining the type of your message, and extracting the response
 /// if let Some(res) = http_response_message.downcast_ref::<HttpResponse>() {
 ///     // do something with the response
 ///     // for example, setting it in the model to be rendered
-///     model.response = Some(res);
+///     model.response = Some(res.0.clone());
 /// }
 /// ```
 pub type Message = Box<dyn Any + Send>;
 
 /// A boxed function or closure that performs computations and optionally dispatches messages.
-/// All commands are processed in their own threads, so blocking commands are totally fine.
+/// All commands are processed on a pool of worker threads, so blocking commands are totally fine.
 /// Frequently, data needs to be passed to commands. Since commands take no arguments,
 /// a common solution to this is to build constructor functions.
 ///
 /// # Example
 ///
 /// ```
+/// # use rustea::Command;
+/// struct HttpResponse(String);
+///
 /// // a constructor function
-/// fn make_request_command(url: &str) -> Command {
-///     // it's okay to block since commands are multi threaded
-///     let text_response = reqwest::blocking::get(url).unwrap().text().unwrap();
-///     
+/// fn make_request_command(url: String) -> Command {
 ///     // the command itself
-///     Box::new(move || Some(Box::new(HttpResponse(text_response))))
+///     Box::new(move || {
+///         // it's okay to block since commands are multi threaded
+///         let text_response = reqwest::blocking::get(url).unwrap().text().unwrap();
+///         Some(Box::new(HttpResponse(text_response)))
+///     })
 /// }
+/// ```
 pub type Command = Box<dyn FnOnce() -> Option<Message> + Send + 'static>;
 
 /// Event representing a terminal resize (x, y).
 /// Boxed as a message so it can be sent to the application.
 pub struct ResizeEvent(pub u16, pub u16);
 
+/// Delivered right before the program is suspended by `command::suspend`,
+/// so the application can save anything it doesn't want to lose, should it never be resumed.
+pub struct SuspendMessage;
+
+/// Delivered once a suspended program has been resumed, and the terminal has been set up again.
+pub struct ResumeMessage;
+
+/// A unix signal the program received. Signals are only delivered on unix.
+///
+/// By default, if `update` returns no command for an `Interrupt`, `Terminate`, or `Hangup`,
+/// the program quits with the exit code a process killed by the signal would have, like 143 for `Terminate`.
+/// So apps that want to save state on the way out can do so, and then return `command::quit`.
+/// This can be turned off with `Program::quit_on_signal`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum SignalMessage {
+    /// SIGINT. In raw mode, pressing Ctrl-C arrives as a key event instead.
+    Interrupt,
+    /// SIGTERM, which is how supervisors usually ask a process to stop.
+    Terminate,
+    /// SIGHUP, sent when the terminal is closed.
+    Hangup,
+    /// SIGWINCH, sent when the terminal is resized. It also arrives as a resize event.
+    WindowChange,
+}
+
+impl SignalMessage {
+    /// The exit code of a process killed by the signal, which is 128 plus the signal's number.
+    /// `None` for signals that don't kill the process by default.
+    pub(crate) fn exit_code(self) -> Option<i32> {
+        match self {
+            SignalMessage::Hangup => Some(128 + 1),
+            SignalMessage::Interrupt => Some(128 + 2),
+            SignalMessage::Terminate => Some(128 + 15),
+            SignalMessage::WindowChange => None,
+        }
+    }
+}
+
+/// The events `rustea` itself produces.
+///
+/// Apps implementing `TypedApp` receive these through their message type's `From<Event>` implementation.
+/// Apps implementing `App` receive the contained event boxed as a `Message` instead,
+/// so a key press arrives as a `KeyEvent`, a resize arrives as a `ResizeEvent`, and so on.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum Event {
+    Key(KeyEvent),
+    Mouse(MouseEvent),
+    /// A terminal resize (x, y).
+    Resize(u16, u16),
+    /// The program is about to be suspended.
+    Suspend,
+    /// The program was resumed after being suspended.
+    Resume,
+    /// The program received a unix signal.
+    Signal(SignalMessage),
+}
+
+impl From<Event> for Message {
+    fn from(event: Event) -> Self {
+        match event {
+            Event::Key(event) => Box::new(event),
+            Event::Mouse(event) => Box::new(event),
+            Event::Resize(x, y) => Box::new(ResizeEvent(x, y)),
+            Event::Suspend => Box::new(SuspendMessage),
+            Event::Resume => Box::new(ResumeMessage),
+            Event::Signal(signal) => Box::new(signal),
+        }
+    }
+}
+
 /// The trait your model must implement in order to be `run`.
 ///
 /// `init` is called once when the model is run for the first time, and optionally returns a `Command`.
@@ -72,8 +168,14 @@ pub struct ResizeEvent(pub u16, pub u16);
 /// It optionally returns a `Command`.
 ///
 /// `view` is called after every `update` and is responsible for rendering the model.
+/// It returns the whole frame as a string, which `rustea` then draws to the terminal,
+/// redrawing only the lines that changed since the last frame.
 /// You are _not_ allowed to mutate the state of your application in the view, only render it.
 ///
+/// `subscriptions` is called after `init` and every `update`, and returns the long lived sources of messages
+/// the application currently wants, like timers. See `Subscription` for details.
+/// There is a default implementation of `subscriptions` that returns none.
+///
 /// For examples, check the `examples` directory.
 pub trait App {
     fn init(&self) -> Option<Command> {
@@ -81,72 +183,109 @@ pub trait App {
     }
 
     fn update(&mut self, msg: Message) -> Option<Command>;
-    fn view(&self, stdout: &mut impl Write);
+    fn view(&self) -> String;
+
+    fn subscriptions(&self) -> Vec<Subscription> {
+        Vec::new()
+    }
 }
 
-/// Runs your application.
+/// Like `App`, but with a message type of your own instead of `Message`.
 ///
-/// This will begin listening for keyboard events, and dispatching them to your application.
-/// These keyboard events are handled by `crossterm`, and are fed into your `update` function as `Message`s.
-/// You can access these keyboard events by simply downcasting them into a `crossterm::event::KeyEvent`.
+/// `update` receives `Msg` values directly, so there is no downcasting,
+/// and the compiler checks that every kind of message is handled.
+/// Key presses, mouse events and resizes are converted into your message type with its `From<Event>` implementation.
+/// Commands are `TypedCommand<Msg>`s, which can only produce your message type, and so are subscriptions.
 ///
-/// `rustea` exports `crossterm`, so you can simply access it with `use rustea::crossterm`.
-pub fn run(app: impl App) -> Result<()> {
-    let mut app = app;
-    let mut stdout = stdout();
+/// Every `App` is also a `TypedApp` with `Message` as its message type, so both kinds can be `run`.
+///
+/// # Example
+///
+/// ```no_run
+/// use rustea::{crossterm::event::KeyCode, Event, TypedApp, TypedCommand};
+///
+/// enum Msg {
+///     Quit,
+///     Other,
+/// }
+///
+/// impl From<Event> for Msg {
+///     fn from(event: Event) -> Self {
+///         match event {
+///             Event::Key(key) if key.code == KeyCode::Esc => Msg::Quit,
+///             _ => Msg::Other,
+///         }
+///     }
+/// }
+///
+/// struct Model;
+///
+/// impl TypedApp for Model {
+///     type Msg = Msg;
+///
+///     fn update(&mut self, msg: Msg) -> Option<TypedCommand<Msg>> {
+///         match msg {
+///             Msg::Quit => Some(TypedCommand::quit()),
+///             Msg::Other => None,
+///         }
+///     }
+///
+///     fn view(&self) -> String {
+///         "Press escape to quit".to_string()
+///     }
+/// }
+///
+/// rustea::run(Model).unwrap();
+/// ```
+pub trait TypedApp {
+    type Msg: From<Event> + Send + 'static;
 
-    let (msg_tx, msg_rx) = mpsc::channel::<Message>();
-    let msg_tx2 = msg_tx.clone();
+    fn init(&self) -> Option<TypedCommand<Self::Msg>> {
+        None
+    }
 
-    let (cmd_tx, cmd_rx) = mpsc::channel::<Command>();
-    let cmd_tx2 = cmd_tx.clone();
+    fn update(&mut self, msg: Self::Msg) -> Option<TypedCommand<Self::Msg>>;
+    fn view(&self) -> String;
 
-    thread::spawn(move || loop {
-        match read().unwrap() {
-            Event::Key(event) => msg_tx.send(Box::new(event)).unwrap(),
-            Event::Mouse(event) => msg_tx.send(Box::new(event)).unwrap(),
-            Event::Resize(x, y) => msg_tx.send(Box::new(ResizeEvent(x, y))).unwrap(),
-        }
-    });
-
-    thread::spawn(move || loop {
-        let cmd = match cmd_rx.recv() {
-            Ok(cmd) => cmd,
-            Err(_) => return,
-        };
-
-        let msg_tx2 = msg_tx2.clone();
-        thread::spawn(move || {
-            if let Some(msg) = cmd() {
-                msg_tx2.send(msg).unwrap();
-            }
-        });
-    });
-
-    initialize(&app, cmd_tx2);
-    app.view(&mut stdout);
-
-    loop {
-        let msg = msg_rx.recv().unwrap();
-        if msg.is::<command::QuitMessage>() {
-            break;
-        } else if msg.is::<command::BatchMessage>() {
-            let batch = msg.downcast::<command::BatchMessage>().unwrap();
-            for cmd in batch.0 {
-                cmd_tx.send(cmd).unwrap();
-            }
-        } else if let Some(cmd) = app.update(msg) {
-            cmd_tx.send(cmd).unwrap();
-        }
+    fn subscriptions(&self) -> Vec<TypedSubscription<Self::Msg>> {
+        Vec::new()
+    }
+}
 
-        app.view(&mut stdout);
+impl<T: App> TypedApp for T {
+    type Msg = Message;
+
+    fn init(&self) -> Option<TypedCommand<Message>> {
+        App::init(self).map(TypedCommand::from_untyped)
     }
 
-    Ok(())
-}
+    fn update(&mut self, msg: Message) -> Option<TypedCommand<Message>> {
+        App::update(self, msg).map(TypedCommand::from_untyped)
+    }
+
+    fn view(&self) -> String {
+        App::view(self)
+    }
 
-fn initialize(app: &impl App, cmd_tx: Sender<Command>) {
-    if let Some(cmd) = app.init() {
-        cmd_tx.send(cmd).unwrap();
+    fn subscriptions(&self) -> Vec<TypedSubscription<Message>> {
+        App::subscriptions(self)
+            .into_iter()
+            .map(TypedSubscription::from_untyped)
+            .collect()
     }
 }
+
+/// Runs your application with the default `Program` options.
+///
+/// This will begin listening for keyboard events, and dispatching them to your application.
+/// These keyboard events are handled by `crossterm`, and are fed into your `update` function as `Message`s.
+/// You can access these keyboard events by simply downcasting them into a `crossterm::event::KeyEvent`.
+///
+/// `rustea` exports `crossterm`, so you can simply access it with `use rustea::crossterm`.
+///
+/// Once the program quits, returns the final state of the app along with the exit code. See `Exit`.
+///
+/// To configure things like the alternate screen or mouse capture, use `Program` instead.
+pub fn run<A: TypedApp>(app: A) -> Result<Exit<A>> {
+    Program::new(app).run()
+}
@@ -0,0 +1,157 @@
+use std::{
+    collections::VecDeque,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc, Condvar, Mutex,
+    },
+    thread,
+};
+
+use crate::scheduler::Job;
+
+/// Counts of the commands a program is processing.
+///
+/// It is shared with the program, so a clone obtained before the program runs stays up to date.
+/// Obtain one with `Program::metrics`.
+#[derive(Debug, Clone, Default)]
+pub struct CommandMetrics {
+    counts: Arc<Counts>,
+}
+
+#[derive(Debug, Default)]
+struct Counts {
+    queued: AtomicUsize,
+    in_flight: AtomicUsize,
+}
+
+impl CommandMetrics {
+    /// The number of commands waiting for a free worker.
+    pub fn queued(&self) -> usize {
+        self.counts.queued.load(Ordering::Relaxed)
+    }
+
+    /// The number of commands currently executing,
+    /// including ones running on dedicated threads from `command::spawn`.
+    pub fn in_flight(&self) -> usize {
+        self.counts.in_flight.load(Ordering::Relaxed)
+    }
+}
+
+/// A fixed number of worker threads that execute jobs from a shared queue.
+///
+/// Workers are started lazily, as jobs are queued while every existing worker is busy.
+/// When the pool is dropped, workers finish the job they are on and exit; queued jobs are discarded.
+pub(crate) struct ThreadPool {
+    shared: Arc<Shared>,
+    size: usize,
+}
+
+struct Shared {
+    state: Mutex<State>,
+    available: Condvar,
+    metrics: CommandMetrics,
+}
+
+struct State {
+    jobs: VecDeque<Job>,
+    workers: usize,
+    idle: usize,
+    closed: bool,
+}
+
+impl ThreadPool {
+    pub(crate) fn new(size: usize, metrics: CommandMetrics) -> Self {
+        Self {
+            shared: Arc::new(Shared {
+                state: Mutex::new(State {
+                    jobs: VecDeque::new(),
+                    workers: 0,
+                    idle: 0,
+                    closed: false,
+                }),
+                available: Condvar::new(),
+                metrics,
+            }),
+            size: size.max(1),
+        }
+    }
+
+    /// Queues a job for the next free worker.
+    pub(crate) fn execute(&self, job: Job) {
+        let mut state = self.shared.lock();
+        state.jobs.push_back(job);
+        self.shared
+            .metrics
+            .counts
+            .queued
+            .fetch_add(1, Ordering::Relaxed);
+
+        // more jobs waiting than idle workers to pick them up
+        if state.jobs.len() > state.idle && state.workers < self.size {
+            state.workers += 1;
+            let shared = self.shared.clone();
+            thread::spawn(move || shared.work());
+        }
+        self.shared.available.notify_one();
+    }
+
+    /// Runs a job on a thread of its own, outside of the pool.
+    pub(crate) fn spawn(&self, job: Job) {
+        let metrics = self.shared.metrics.clone();
+        metrics.counts.in_flight.fetch_add(1, Ordering::Relaxed);
+        thread::spawn(move || {
+            job();
+            metrics.counts.in_flight.fetch_sub(1, Ordering::Relaxed);
+        });
+    }
+}
+
+impl Drop for ThreadPool {
+    fn drop(&mut self) {
+        let mut state = self.shared.lock();
+        state.closed = true;
+        let discarded = state.jobs.len();
+        state.jobs.clear();
+        self.shared
+            .metrics
+            .counts
+            .queued
+            .fetch_sub(discarded, Ordering::Relaxed);
+        self.shared.available.notify_all();
+    }
+}
+
+impl Shared {
+    fn lock(&self) -> std::sync::MutexGuard<'_, State> {
+        // jobs never run while the lock is held, so it can't be poisoned by a panicking job
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn work(&self) {
+        loop {
+            let job = {
+                let mut state = self.lock();
+                loop {
+                    if state.closed {
+                        return;
+                    }
+                    if let Some(job) = state.jobs.pop_front() {
+                        break job;
+                    }
+                    state.idle += 1;
+                    state = self
+                        .available
+                        .wait(state)
+                        .unwrap_or_else(|e| e.into_inner());
+                    state.idle -= 1;
+                }
+            };
+
+            let counts = &self.metrics.counts;
+            counts.queued.fetch_sub(1, Ordering::Relaxed);
+            counts.in_flight.fetch_add(1, Ordering::Relaxed);
+            job();
+            counts.in_flight.fetch_sub(1, Ordering::Relaxed);
+        }
+    }
+}
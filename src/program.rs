@@ -0,0 +1,542 @@
+use std::{
+    fmt,
+    io::{self, Write},
+    marker::PhantomData,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        mpsc::{self, Receiver, RecvTimeoutError, Sender},
+        Arc,
+    },
+    time::{Duration, Instant},
+};
+
+#[cfg(unix)]
+use crate::signal;
+use crate::{
+    command::{self, ExecFinished, QuitMessage},
+    input::{EventSource, TerminalInput},
+    pool::{CommandMetrics, ThreadPool},
+    reader::EventReader,
+    renderer::{Mode, Renderer},
+    runtime::{Flow, Runtime},
+    scheduler::Threaded,
+    terminal::{Terminal, TerminalOptions},
+    Error, Event, Message, Result, TypedApp,
+};
+
+pub(crate) const DEFAULT_WORKERS: usize = 16;
+
+/// Which mouse events the terminal should report to your application.
+///
+/// Mouse events are fed into `update` as `crossterm::event::MouseEvent` messages.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum MouseMode {
+    /// No mouse events are reported. This is the default.
+    #[default]
+    None,
+    /// Clicks, releases, scrolling, and motion while a button is held down.
+    CellMotion,
+    /// Everything in `CellMotion`, plus motion with no buttons held down.
+    AllMotion,
+}
+
+/// What a program hands back once it has quit.
+///
+/// # Example
+///
+/// ```no_run
+/// # use rustea::{App, Command, Message};
+/// # struct Picker { selected: Option<String> }
+/// # impl App for Picker {
+/// #     fn update(&mut self, _msg: Message) -> Option<Command> { None }
+/// #     fn view(&self) -> String { String::new() }
+/// # }
+/// let exit = rustea::run(Picker { selected: None }).unwrap();
+/// match exit.app.selected {
+///     Some(choice) => println!("{}", choice),
+///     None => std::process::exit(exit.code),
+/// }
+/// ```
+#[derive(Debug)]
+pub struct Exit<A> {
+    /// The app, as it was when the program quit.
+    pub app: A,
+    /// The code passed to `command::quit_with`, or 0 for `command::quit`.
+    pub code: i32,
+}
+
+/// A builder for configuring how your application is run.
+///
+/// `rustea::run` is a shorthand for `Program::new(app).run()` with the default options.
+///
+/// # Example
+///
+/// ```no_run
+/// use rustea::{App, Command, Message, MouseMode, Program};
+///
+/// struct Model;
+///
+/// impl App for Model {
+///     fn update(&mut self, _msg: Message) -> Option<Command> {
+///         None
+///     }
+///
+///     fn view(&self) -> String {
+///         "Hello!".to_string()
+///     }
+/// }
+///
+/// Program::new(Model)
+///     .alt_screen(true)
+///     .mouse(MouseMode::AllMotion)
+///     .run()
+///     .unwrap();
+/// ```
+pub struct Program<A: TypedApp> {
+    app: A,
+    alt_screen: bool,
+    inline: bool,
+    mouse: MouseMode,
+    input: Option<Box<dyn EventSource>>,
+    output: Option<Box<dyn Write + Send>>,
+    raw_mode: Option<bool>,
+    fps: u32,
+    immediate_render: bool,
+    quit_on_signal: bool,
+    detached: bool,
+    workers: usize,
+    metrics: CommandMetrics,
+    #[cfg(feature = "tokio")]
+    tokio_handle: Option<tokio::runtime::Handle>,
+    msg_tx: Sender<Message>,
+    msg_rx: Receiver<Message>,
+    running: Arc<AtomicBool>,
+}
+
+impl<A: TypedApp> Program<A> {
+    /// Creates a program for the given app, rendering to stdout in the alternate screen with no mouse capture.
+    pub fn new(app: A) -> Self {
+        let (msg_tx, msg_rx) = mpsc::channel();
+        Self {
+            app,
+            alt_screen: true,
+            inline: false,
+            mouse: MouseMode::None,
+            input: None,
+            output: None,
+            raw_mode: None,
+            fps: 60,
+            immediate_render: false,
+            quit_on_signal: true,
+            detached: false,
+            workers: DEFAULT_WORKERS,
+            metrics: CommandMetrics::default(),
+            #[cfg(feature = "tokio")]
+            tokio_handle: None,
+            msg_tx,
+            msg_rx,
+            running: Arc::new(AtomicBool::new(false)),
+        }
+    }
+
+    /// Whether to render in the terminal's alternate screen. Defaults to `true`.
+    /// The original screen is restored when the program exits.
+    pub fn alt_screen(mut self, alt_screen: bool) -> Self {
+        self.alt_screen = alt_screen;
+        self
+    }
+
+    /// Whether to render inline, below the cursor, instead of taking over the whole screen. Defaults to `false`.
+    ///
+    /// An inline program only redraws the lines its frame takes up, and leaves the final frame in the scrollback
+    /// once it quits, which suits small prompts. Inline programs never use the alternate screen,
+    /// so this takes precedence over `alt_screen`.
+    pub fn inline(mut self, inline: bool) -> Self {
+        self.inline = inline;
+        self
+    }
+
+    /// Which mouse events to capture. Defaults to `MouseMode::None`.
+    pub fn mouse(mut self, mouse: MouseMode) -> Self {
+        self.mouse = mouse;
+        self
+    }
+
+    /// Where to read events from. Defaults to the terminal the process runs in, see `input::TerminalInput`.
+    ///
+    /// Together with `output`, this allows running the application over a pty or a socket.
+    /// The terminal the process runs in is then left out of raw mode, unless `raw_mode` says otherwise.
+    pub fn input(mut self, input: impl EventSource + 'static) -> Self {
+        self.input = Some(Box::new(input));
+        self
+    }
+
+    /// Where to render the application. Defaults to stdout.
+    pub fn output(mut self, output: impl Write + Send + 'static) -> Self {
+        self.output = Some(Box::new(output));
+        self
+    }
+
+    /// Whether to put the terminal the process runs in into raw mode while the program runs.
+    /// Defaults to `true`, unless a custom `input` is used.
+    ///
+    /// Raw mode makes the terminal send every key press right away, instead of a line at a time,
+    /// and stops it from echoing them. It is only needed when the events come from that terminal.
+    pub fn raw_mode(mut self, raw_mode: bool) -> Self {
+        self.raw_mode = Some(raw_mode);
+        self
+    }
+
+    /// The maximum number of frames drawn per second. Defaults to 60.
+    ///
+    /// Messages that arrive between two frames are all applied through `update` before the next frame is drawn,
+    /// so a burst of messages doesn't cause a burst of redraws.
+    pub fn fps(mut self, fps: u32) -> Self {
+        self.fps = fps.max(1);
+        self
+    }
+
+    /// Whether to draw a frame right after every `update`, instead of limiting the frame rate.
+    /// Defaults to `false`.
+    pub fn immediate_render(mut self, immediate: bool) -> Self {
+        self.immediate_render = immediate;
+        self
+    }
+
+    /// Whether to quit when `update` returns no command for a `SignalMessage` that would have killed the process,
+    /// like `Terminate`. Defaults to `true`.
+    ///
+    /// The program then quits cleanly, restoring the terminal, with the exit code the signal would have caused.
+    /// When turned off, such signals are only delivered to `update`.
+    pub fn quit_on_signal(mut self, quit: bool) -> Self {
+        self.quit_on_signal = quit;
+        self
+    }
+
+    /// The number of worker threads commands are executed on. Defaults to 16.
+    ///
+    /// Commands are queued until a worker is free, so a big `command::batch` doesn't start a thread per command.
+    /// Commands that block for a long time can be given a dedicated thread with `command::spawn`,
+    /// so they don't hold up a worker.
+    pub fn workers(mut self, workers: usize) -> Self {
+        self.workers = workers.max(1);
+        self
+    }
+
+    /// Returns a handle to the number of queued and in flight commands.
+    /// It keeps updating while the program runs.
+    pub fn metrics(&self) -> CommandMetrics {
+        self.metrics.clone()
+    }
+
+    /// Returns a handle for sending messages to the program from outside of it,
+    /// like from a background service or another library's callback.
+    ///
+    /// Messages sent before the program runs are delivered once it does.
+    pub fn sender(&self) -> ProgramHandle<A::Msg> {
+        ProgramHandle {
+            msg_tx: self.msg_tx.clone(),
+            running: self.running.clone(),
+            msg: PhantomData,
+        }
+    }
+
+    /// Runs the program away from the terminal the process was started from, like over a socket.
+    /// It then doesn't listen for the process's signals, `command::suspend` does nothing,
+    /// and `command::exec` finishes with an error right away, since none of them concern the program's own terminal.
+    pub(crate) fn detached(mut self) -> Self {
+        self.detached = true;
+        self
+    }
+
+    /// The tokio runtime to run futures from `command::future` on.
+    /// By default, the program creates its own runtime the first time a future is run.
+    ///
+    /// Only available with the `tokio` feature.
+    #[cfg(feature = "tokio")]
+    pub fn tokio_handle(mut self, handle: tokio::runtime::Handle) -> Self {
+        self.tokio_handle = Some(handle);
+        self
+    }
+
+    /// Runs the application with the configured options.
+    ///
+    /// This will begin listening for keyboard events, and dispatching them to your application.
+    /// These keyboard events are read from the `input`, and are fed into your `update` function as `Message`s.
+    /// You can access these keyboard events by simply downcasting them into a `crossterm::event::KeyEvent`.
+    ///
+    /// The terminal is put into raw mode and the cursor is hidden for as long as the program runs.
+    /// It is restored once the program quits, returns an error, or panics.
+    ///
+    /// Once the program quits and the terminal is restored, returns the final state of the app, and the exit code.
+    /// Returns an error if the terminal can't be written to or read from, or if a command panics.
+    pub fn run(self) -> Result<Exit<A>> {
+        let Program {
+            app,
+            alt_screen,
+            inline,
+            mouse,
+            input,
+            output,
+            raw_mode,
+            fps,
+            immediate_render,
+            quit_on_signal,
+            detached,
+            workers,
+            metrics,
+            #[cfg(feature = "tokio")]
+            tokio_handle,
+            msg_tx,
+            msg_rx,
+            running,
+        } = self;
+        let _running = Running::start(running);
+        #[cfg(unix)]
+        let _signals = (!detached)
+            .then(|| signal::listen(msg_tx.clone()))
+            .transpose()?;
+        let frame_interval = (!immediate_render).then(|| Duration::from_secs(1) / fps);
+
+        let options = TerminalOptions {
+            raw_mode: raw_mode.unwrap_or(input.is_none()),
+            alt_screen: alt_screen && !inline,
+            mouse,
+        };
+        let mut terminal = Terminal::new(output, options);
+        terminal.enter()?;
+        let mode = if inline {
+            Mode::Inline
+        } else {
+            Mode::Fullscreen
+        };
+        let input = input.unwrap_or_else(|| Box::new(TerminalInput));
+        let mut renderer = Renderer::new(mode, input.size());
+
+        let reader = EventReader::start(input, msg_tx.clone());
+
+        let scheduler = Threaded::new(ThreadPool::new(workers, metrics), msg_tx.clone());
+        let mut runtime = Runtime::new(app, Box::new(scheduler), msg_tx.clone());
+        runtime.set_quit_on_signal(quit_on_signal);
+        #[cfg(feature = "tokio")]
+        if let Some(handle) = tokio_handle {
+            runtime.set_tokio_handle(handle);
+        }
+        runtime.init();
+        renderer.render(&mut terminal, &runtime.app().view())?;
+        let mut last_render = Instant::now();
+        let mut dirty = false;
+
+        let code = loop {
+            // while a frame is pending, only wait until it is due
+            let msg = match frame_interval {
+                Some(interval) if dirty => {
+                    let due = (last_render + interval).saturating_duration_since(Instant::now());
+                    match msg_rx.recv_timeout(due) {
+                        Ok(msg) => Some(msg),
+                        Err(RecvTimeoutError::Timeout) => None,
+                        Err(RecvTimeoutError::Disconnected) => return Err(Error::ChannelClosed),
+                    }
+                }
+                _ => Some(msg_rx.recv().map_err(|_| Error::ChannelClosed)?),
+            };
+
+            let mut next = msg;
+            let mut quit = None;
+            while let Some(msg) = next.take() {
+                if let Some(&Event::Resize(x, y)) = msg.downcast_ref::<Event>() {
+                    renderer.resize(x, y);
+                }
+
+                let flow = runtime.handle(msg)?;
+                dirty = true;
+                match flow {
+                    Flow::Print(text) => renderer.print(&mut terminal, &text)?,
+                    Flow::Suspend if cfg!(unix) && !detached => {
+                        runtime.handle(Box::new(Event::Suspend))?;
+                        let frame = runtime.app().view();
+                        release(&mut terminal, &mut renderer, &reader, &frame, stop_process)??;
+                        let _ = msg_tx.send(Box::new(Event::Resume));
+                    }
+                    Flow::Exec(_, done) if detached => {
+                        // the process would run in the terminal the server was started from
+                        let status = Err(io::Error::new(
+                            io::ErrorKind::Unsupported,
+                            "processes can't be run from a program that is served over a socket",
+                        ));
+                        let _ = msg_tx.send(done(ExecFinished { status }));
+                    }
+                    Flow::Exec(mut process, done) => {
+                        let frame = runtime.app().view();
+                        let status =
+                            release(&mut terminal, &mut renderer, &reader, &frame, || {
+                                #[cfg(unix)]
+                                let _interrupts = signal::ignore_interrupts();
+                                process.status()
+                            })?;
+                        let _ = msg_tx.send(done(ExecFinished { status }));
+                    }
+                    Flow::Quit(code) => {
+                        quit = Some(code);
+                        break;
+                    }
+                    Flow::Suspend | Flow::Continue => (),
+                }
+
+                // apply what is already waiting, so a burst of messages costs one frame,
+                // but only until that frame is due, so a steady stream of messages doesn't hold it up
+                next = match frame_interval {
+                    Some(interval) if last_render.elapsed() < interval => msg_rx.try_recv().ok(),
+                    _ => None,
+                };
+            }
+
+            if let Some(code) = quit {
+                break code;
+            }
+
+            let due = match frame_interval {
+                Some(interval) => last_render.elapsed() >= interval,
+                None => true,
+            };
+            if dirty && due {
+                renderer.render(&mut terminal, &runtime.app().view())?;
+                last_render = Instant::now();
+                dirty = false;
+            }
+        };
+
+        if dirty {
+            renderer.render(&mut terminal, &runtime.app().view())?;
+        }
+        renderer.finish(&mut terminal)?;
+
+        terminal.restore()?;
+        Ok(Exit {
+            app: runtime.into_app(),
+            code,
+        })
+    }
+}
+
+/// A handle for sending messages to a program from outside of it. Obtained with `Program::sender`.
+///
+/// It can be cloned, and sent to other threads.
+///
+/// # Example
+///
+/// ```no_run
+/// # use rustea::{App, Command, Message, Program};
+/// # struct Model;
+/// # impl App for Model {
+/// #     fn update(&mut self, _msg: Message) -> Option<Command> { None }
+/// #     fn view(&self) -> String { String::new() }
+/// # }
+/// struct JobFinished;
+///
+/// let program = Program::new(Model);
+/// let handle = program.sender();
+///
+/// std::thread::spawn(move || {
+///     // some work, outside of the program
+///     handle.send(Box::new(JobFinished));
+/// });
+///
+/// program.run().unwrap();
+/// ```
+pub struct ProgramHandle<M = Message> {
+    msg_tx: Sender<Message>,
+    running: Arc<AtomicBool>,
+    msg: PhantomData<fn(M)>,
+}
+
+impl<M: Send + 'static> ProgramHandle<M> {
+    /// Sends a message to the program, which arrives in `update` like any other.
+    /// For apps implementing `TypedApp`, this is their message type.
+    ///
+    /// Returns `false` if the program has already exited, in which case the message is dropped.
+    pub fn send(&self, msg: M) -> bool {
+        self.msg_tx.send(command::erase(msg)).is_ok()
+    }
+
+    /// Makes the program quit with an exit code of 0, just like `command::quit`.
+    ///
+    /// Returns `false` if the program has already exited.
+    pub fn quit(&self) -> bool {
+        self.msg_tx.send(Box::new(QuitMessage(0))).is_ok()
+    }
+
+    /// Whether the program is currently running.
+    pub fn is_running(&self) -> bool {
+        self.running.load(Ordering::Relaxed)
+    }
+}
+
+impl<M> Clone for ProgramHandle<M> {
+    fn clone(&self) -> Self {
+        Self {
+            msg_tx: self.msg_tx.clone(),
+            running: self.running.clone(),
+            msg: PhantomData,
+        }
+    }
+}
+
+impl<M> fmt::Debug for ProgramHandle<M> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ProgramHandle")
+            .field("running", &self.running.load(Ordering::Relaxed))
+            .finish_non_exhaustive()
+    }
+}
+
+/// Gives the terminal back to the user while `f` runs, and then takes it over again.
+///
+/// The given frame is drawn first, so that an inline frame is left behind up to date, like it would be when quitting.
+/// Afterwards, the next frame is drawn from scratch, since the screen may have been changed in the meantime.
+fn release<T>(
+    terminal: &mut Terminal,
+    renderer: &mut Renderer,
+    reader: &EventReader,
+    frame: &str,
+    f: impl FnOnce() -> T,
+) -> io::Result<T> {
+    renderer.render(terminal, frame)?;
+    renderer.finish(terminal)?;
+    reader.pause();
+    terminal.restore()?;
+
+    let result = f();
+
+    terminal.enter()?;
+    reader.resume();
+    renderer.invalidate();
+    Ok(result)
+}
+
+/// Stops the process with SIGTSTP, and returns once it is continued with SIGCONT.
+#[cfg(unix)]
+fn stop_process() -> io::Result<()> {
+    signal_hook::low_level::raise(signal_hook::consts::SIGTSTP)
+}
+
+#[cfg(not(unix))]
+fn stop_process() -> io::Result<()> {
+    Ok(())
+}
+
+/// Marks the program as running, until dropped.
+struct Running(Arc<AtomicBool>);
+
+impl Running {
+    fn start(running: Arc<AtomicBool>) -> Self {
+        running.store(true, Ordering::Relaxed);
+        Self(running)
+    }
+}
+
+impl Drop for Running {
+    fn drop(&mut self) {
+        self.0.store(false, Ordering::Relaxed);
+    }
+}
@@ -0,0 +1,114 @@
+use std::{
+    sync::{mpsc::Sender, Arc, Condvar, Mutex, MutexGuard},
+    thread,
+    time::Duration,
+};
+
+use crate::{input::EventSource, runtime::EventReadFailed, Message};
+
+/// How long the reader waits for an event before checking whether it was paused or stopped.
+const POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+/// Reads events from an `EventSource` on a dedicated thread, and sends them to the program.
+///
+/// The reader can be paused, so that something else, like a child process, can read from the terminal.
+/// It stops once dropped.
+pub(crate) struct EventReader {
+    shared: Arc<Shared>,
+}
+
+struct Shared {
+    state: Mutex<State>,
+    changed: Condvar,
+}
+
+#[derive(Default)]
+struct State {
+    paused: bool,
+    stopped: bool,
+    /// Whether the thread is waiting, and not reading from the terminal.
+    idle: bool,
+}
+
+impl EventReader {
+    pub(crate) fn start(mut source: Box<dyn EventSource>, msg_tx: Sender<Message>) -> Self {
+        let shared = Arc::new(Shared {
+            state: Mutex::new(State::default()),
+            changed: Condvar::new(),
+        });
+
+        let thread_shared = shared.clone();
+        thread::spawn(move || {
+            read_events(&mut *source, &thread_shared, &msg_tx);
+            // nothing is going to be read anymore, so there's no need to wait for the thread to pause
+            let mut state = thread_shared.lock();
+            state.idle = true;
+            thread_shared.changed.notify_all();
+        });
+
+        Self { shared }
+    }
+
+    /// Stops reading events, and waits until the terminal is no longer being read from.
+    pub(crate) fn pause(&self) {
+        let mut state = self.shared.lock();
+        state.paused = true;
+        self.shared.changed.notify_all();
+        while !state.idle {
+            state = self.shared.wait(state);
+        }
+    }
+
+    /// Starts reading events again after a pause.
+    pub(crate) fn resume(&self) {
+        self.shared.lock().paused = false;
+        self.shared.changed.notify_all();
+    }
+}
+
+impl Drop for EventReader {
+    fn drop(&mut self) {
+        self.shared.lock().stopped = true;
+        self.shared.changed.notify_all();
+    }
+}
+
+impl Shared {
+    fn lock(&self) -> MutexGuard<'_, State> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn wait<'a>(&self, state: MutexGuard<'a, State>) -> MutexGuard<'a, State> {
+        self.changed.wait(state).unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+fn read_events(source: &mut dyn EventSource, shared: &Shared, msg_tx: &Sender<Message>) {
+    loop {
+        {
+            let mut state = shared.lock();
+            while state.paused && !state.stopped {
+                state.idle = true;
+                shared.changed.notify_all();
+                state = shared.wait(state);
+            }
+            if state.stopped {
+                return;
+            }
+            state.idle = false;
+        }
+
+        let msg: Message = match source.read(POLL_INTERVAL) {
+            Ok(Some(event)) => Box::new(event),
+            Ok(None) => continue,
+            Err(err) => {
+                // the main loop turns this into an error, there's nothing left to read
+                let _ = msg_tx.send(Box::new(EventReadFailed(err)));
+                return;
+            }
+        };
+        if msg_tx.send(msg).is_err() {
+            return;
+        }
+    }
+}
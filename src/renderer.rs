@@ -0,0 +1,413 @@
+use std::io::{Result, Write};
+
+use crossterm::{
+    cursor::{MoveTo, MoveUp},
+    queue,
+    style::Print,
+    terminal::{Clear, ClearType},
+};
+
+/// Where frames are drawn.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Mode {
+    /// Over the whole screen, starting at the top left corner.
+    Fullscreen,
+    /// Starting at the cursor, in only as many rows as the frame needs, leaving the rest of the screen alone.
+    Inline,
+}
+
+/// Draws frames to the terminal, redrawing only the lines that changed since the previous frame.
+///
+/// All output for a frame is queued and then flushed once, so the terminal never shows a half drawn frame.
+pub(crate) struct Renderer {
+    mode: Mode,
+    lines: Vec<String>,
+    /// The number of rows the previous frame took up, counting wrapped lines. Only used inline.
+    rows: usize,
+    /// The size of the terminal, if known.
+    size: Option<(u16, u16)>,
+    clear: bool,
+}
+
+impl Renderer {
+    pub(crate) fn new(mode: Mode, size: Option<(u16, u16)>) -> Self {
+        Self {
+            mode,
+            lines: Vec::new(),
+            rows: 0,
+            size,
+            clear: mode == Mode::Fullscreen,
+        }
+    }
+
+    /// Forgets the previous frame, so the next render clears the screen and draws everything.
+    /// Inline, only the rows of the previous frame are cleared.
+    ///
+    /// This is needed whenever the screen may have changed behind the renderer's back, like on a resize.
+    pub(crate) fn invalidate(&mut self) {
+        self.lines.clear();
+        self.clear = true;
+    }
+
+    /// Updates the size of the terminal, which also invalidates the previous frame.
+    pub(crate) fn resize(&mut self, width: u16, height: u16) {
+        self.size = Some((width, height));
+        self.invalidate();
+    }
+
+    /// Draws the given frame.
+    pub(crate) fn render(&mut self, output: &mut impl Write, frame: &str) -> Result<()> {
+        let lines: Vec<String> = frame
+            .split('\n')
+            .map(|line| expand_tabs(line.strip_suffix('\r').unwrap_or(line)))
+            .collect();
+
+        match self.mode {
+            Mode::Fullscreen => self.render_fullscreen(output, lines)?,
+            Mode::Inline => self.render_inline(output, lines)?,
+        }
+        output.flush()
+    }
+
+    /// Prints text above the frame, where it stays, and draws the previous frame again below it.
+    /// Only inline, since a fullscreen frame has no room above it.
+    pub(crate) fn print(&mut self, output: &mut impl Write, text: &str) -> Result<()> {
+        if self.mode != Mode::Inline {
+            return Ok(());
+        }
+
+        queue!(output, Print('\r'))?;
+        if self.rows > 1 {
+            queue!(output, MoveUp(row(self.rows - 1)))?;
+        }
+        queue!(output, Clear(ClearType::FromCursorDown))?;
+        for line in text.split('\n') {
+            let line = line.strip_suffix('\r').unwrap_or(line);
+            queue!(output, Print(line), Print("\r\n"))?;
+        }
+
+        // the frame itself didn't change, so what was drawn before is drawn again, and diffed against as usual
+        let lines = std::mem::take(&mut self.lines);
+        self.rows = 0;
+        self.clear = false;
+        self.render_inline(output, lines)?;
+        output.flush()
+    }
+
+    /// Moves the cursor below the last frame, so that whatever is printed after the program
+    /// doesn't overwrite it. Only needed inline, since the alternate screen is left behind as a whole.
+    pub(crate) fn finish(&mut self, output: &mut impl Write) -> Result<()> {
+        if self.mode == Mode::Inline && self.rows > 0 {
+            queue!(output, Print("\r\n"))?;
+            self.rows = 0;
+            self.lines.clear();
+        }
+        output.flush()
+    }
+
+    fn render_fullscreen(&mut self, output: &mut impl Write, mut lines: Vec<String>) -> Result<()> {
+        // what doesn't fit would wrap onto the row below, or be drawn over the bottom row
+        if let Some((width, height)) = self.size.filter(|&(width, height)| width > 0 && height > 0)
+        {
+            lines.truncate(usize::from(height));
+            for line in &mut lines {
+                *line = clip_line(line, width, true);
+            }
+        }
+
+        if self.clear {
+            queue!(output, Clear(ClearType::All))?;
+            self.clear = false;
+        }
+
+        for (i, line) in lines.iter().enumerate() {
+            if self.lines.get(i) != Some(line) {
+                queue!(
+                    output,
+                    MoveTo(0, row(i)),
+                    Print(line),
+                    Clear(ClearType::UntilNewLine)
+                )?;
+            }
+        }
+
+        if lines.len() < self.lines.len() {
+            queue!(
+                output,
+                MoveTo(0, row(lines.len())),
+                Clear(ClearType::FromCursorDown)
+            )?;
+        }
+
+        self.lines = lines;
+        Ok(())
+    }
+
+    /// Draws the frame from the top of the previous one, which is found by moving up the rows it took up.
+    /// Between frames, the cursor rests at the start of the frame's last row.
+    fn render_inline(&mut self, output: &mut impl Write, mut lines: Vec<String>) -> Result<()> {
+        // the cursor can't move above the top of the screen, so only the bottom of a tall frame is shown
+        if let Some((_, height)) = self.size {
+            let height = usize::from(height.max(1));
+            let mut rows: usize = lines.iter().map(|line| self.rows_of(line)).sum();
+            while rows > height && lines.len() > 1 {
+                rows -= self.rows_of(&lines.remove(0));
+            }
+        }
+
+        queue!(output, Print('\r'))?;
+        if self.rows > 1 {
+            queue!(output, MoveUp(row(self.rows - 1)))?;
+        }
+        if self.clear {
+            queue!(output, Clear(ClearType::FromCursorDown))?;
+            self.clear = false;
+        }
+
+        let mut rows = 0;
+        // once a line wraps onto a different number of rows, every line below it has moved
+        let mut shifted = false;
+        for (i, line) in lines.iter().enumerate() {
+            if i > 0 {
+                queue!(output, Print("\r\n"))?;
+            }
+
+            let line_rows = self.rows_of(line);
+            match self.lines.get(i) {
+                Some(previous) if previous == line && !shifted => {
+                    for _ in 1..line_rows {
+                        queue!(output, Print("\r\n"))?;
+                    }
+                }
+                previous => {
+                    if let Some(previous) = previous {
+                        shifted |= self.rows_of(previous) != line_rows;
+                    }
+                    queue!(output, Print(line), Clear(ClearType::UntilNewLine))?;
+                }
+            }
+            rows += line_rows;
+        }
+
+        if rows < self.rows {
+            // clear what's left of the previous frame below this one
+            queue!(
+                output,
+                Print("\r\n"),
+                Clear(ClearType::FromCursorDown),
+                MoveUp(1)
+            )?;
+        }
+        queue!(output, Print('\r'))?;
+
+        self.lines = lines;
+        self.rows = rows;
+        Ok(())
+    }
+
+    /// The number of rows a line takes up once the terminal wraps it.
+    fn rows_of(&self, line: &str) -> usize {
+        match self.size {
+            Some((width, _)) if width > 0 => {
+                let width = usize::from(width);
+                display_width(line).div_ceil(width)
+            }
+            _ => 1,
+        }
+        .max(1)
+    }
+}
+
+fn row(i: usize) -> u16 {
+    i.try_into().unwrap_or(u16::MAX)
+}
+
+/// Terminals put tab stops every 8 columns.
+const TAB_WIDTH: usize = 8;
+
+/// The number of columns a tab takes up, when it starts at the given column.
+fn tab_width(column: usize) -> usize {
+    TAB_WIDTH - column % TAB_WIDTH
+}
+
+/// Replaces tabs with spaces up to the next tab stop, since a tab only moves the cursor,
+/// and would leave what was drawn there before on the screen.
+fn expand_tabs(line: &str) -> String {
+    let mut expanded = String::with_capacity(line.len());
+    for c in line.chars() {
+        if c == '\t' {
+            let spaces = tab_width(display_width(&expanded));
+            expanded.extend(std::iter::repeat_n(' ', spaces));
+        } else {
+            expanded.push(c);
+        }
+    }
+    expanded
+}
+
+/// The number of columns a line takes up, leaving out escape sequences like colors, and expanding tabs.
+fn display_width(line: &str) -> usize {
+    let mut width = 0;
+    let mut chars = line.chars();
+    while let Some(c) = chars.next() {
+        if c == '\x1b' {
+            // skip the sequence up to and including its final byte
+            if chars.next() == Some('[') {
+                for c in chars.by_ref() {
+                    if ('@'..='~').contains(&c) {
+                        break;
+                    }
+                }
+            }
+        } else if c == '\t' {
+            width += tab_width(width);
+        } else if !c.is_control() {
+            width += 1;
+        }
+    }
+    width
+}
+
+/// Cuts the line off at the given width. Escape sequences take up no room,
+/// and are either kept, even past the cut, so that styles are still reset, or left out.
+/// Tabs are expanded to spaces, like `Renderer::render` does.
+pub(crate) fn clip_line(line: &str, width: u16, styled: bool) -> String {
+    let mut clipped = String::new();
+    let mut room = usize::from(width);
+    let mut chars = line.chars();
+    while let Some(c) = chars.next() {
+        if c == '\x1b' {
+            let mut sequence = String::from(c);
+            if let Some(next) = chars.next() {
+                sequence.push(next);
+                if next == '[' {
+                    for c in chars.by_ref() {
+                        sequence.push(c);
+                        if ('@'..='~').contains(&c) {
+                            break;
+                        }
+                    }
+                }
+            }
+            if styled {
+                clipped.push_str(&sequence);
+            }
+        } else if c == '\t' {
+            let spaces = tab_width(usize::from(width) - room).min(room);
+            clipped.extend(std::iter::repeat_n(' ', spaces));
+            room -= spaces;
+        } else if room > 0 && !c.is_control() {
+            clipped.push(c);
+            room -= 1;
+        }
+    }
+    clipped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn render(renderer: &mut Renderer, frame: &str) -> String {
+        let mut output = Vec::new();
+        renderer.render(&mut output, frame).unwrap();
+        String::from_utf8(output).unwrap()
+    }
+
+    #[test]
+    fn inline_grows_and_shrinks() {
+        let mut renderer = Renderer::new(Mode::Inline, Some((10, 5)));
+        assert_eq!(render(&mut renderer, "a\nb"), "\ra\x1b[K\r\nb\x1b[K\r");
+        // only the new line is drawn
+        assert_eq!(
+            render(&mut renderer, "a\nb\nc"),
+            "\r\x1b[1A\r\n\r\nc\x1b[K\r"
+        );
+        // the rows below the frame are cleared, and the cursor goes back to its last row
+        assert_eq!(render(&mut renderer, "a"), "\r\x1b[2A\r\n\x1b[J\x1b[1A\r");
+        assert_eq!(render(&mut renderer, "b"), "\rb\x1b[K\r");
+    }
+
+    #[test]
+    fn inline_wraps() {
+        let mut renderer = Renderer::new(Mode::Inline, Some((4, 5)));
+        assert_eq!(
+            render(&mut renderer, "abcdef\nx"),
+            "\rabcdef\x1b[K\r\nx\x1b[K\r"
+        );
+        // still two rows, so the line below stays where it was
+        assert_eq!(
+            render(&mut renderer, "abcdefgh\nx"),
+            "\r\x1b[2Aabcdefgh\x1b[K\r\n\r"
+        );
+        // one row less, so the line below moved up and is drawn again
+        assert_eq!(
+            render(&mut renderer, "abc\nx"),
+            "\r\x1b[2Aabc\x1b[K\r\nx\x1b[K\r\n\x1b[J\x1b[1A\r"
+        );
+        // styles take up no room
+        assert_eq!(
+            render(&mut renderer, "\x1b[31mabcd\x1b[0m\nx"),
+            "\r\x1b[1A\x1b[31mabcd\x1b[0m\x1b[K\r\n\r"
+        );
+    }
+
+    #[test]
+    fn inline_shows_the_bottom_of_tall_frames() {
+        let mut renderer = Renderer::new(Mode::Inline, Some((10, 2)));
+        assert_eq!(render(&mut renderer, "a\nb\nc"), "\rb\x1b[K\r\nc\x1b[K\r");
+    }
+
+    #[test]
+    fn inline_print() {
+        let mut renderer = Renderer::new(Mode::Inline, Some((10, 5)));
+        render(&mut renderer, "a\nb");
+        let mut output = Vec::new();
+        renderer.print(&mut output, "hello").unwrap();
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            "\r\x1b[1A\x1b[Jhello\r\n\ra\x1b[K\r\nb\x1b[K\r"
+        );
+    }
+
+    #[test]
+    fn tabs() {
+        let mut renderer = Renderer::new(Mode::Fullscreen, Some((10, 5)));
+        assert_eq!(
+            render(&mut renderer, "Name\tValue\n\x1b[1mab\x1b[0m\tc"),
+            "\x1b[2J\x1b[1;1HName    Va\x1b[K\x1b[2;1H\x1b[1mab\x1b[0m      c\x1b[K"
+        );
+
+        // a tab reaching past the edge makes the line wrap
+        let mut renderer = Renderer::new(Mode::Inline, Some((8, 5)));
+        assert_eq!(
+            render(&mut renderer, "abc\td\nx"),
+            "\rabc     d\x1b[K\r\nx\x1b[K\r"
+        );
+        assert_eq!(
+            render(&mut renderer, "abc\td\ny"),
+            "\r\x1b[2A\r\n\r\ny\x1b[K\r"
+        );
+
+        assert_eq!(clip_line("\tab\tc", 20, false), "        ab      c");
+        assert_eq!(clip_line("ab\tc", 5, false), "ab   ");
+    }
+
+    #[test]
+    fn fullscreen_clips() {
+        let mut renderer = Renderer::new(Mode::Fullscreen, Some((3, 2)));
+        assert_eq!(
+            render(&mut renderer, "abcdef\n\x1b[31mxyzw\x1b[0m\nthird"),
+            "\x1b[2J\x1b[1;1Habc\x1b[K\x1b[2;1H\x1b[31mxyz\x1b[0m\x1b[K"
+        );
+        // only the line that changed within what is shown is drawn
+        assert_eq!(
+            render(&mut renderer, "abcxyz\nxy\nfourth"),
+            "\x1b[2;1Hxy\x1b[K"
+        );
+        assert_eq!(
+            render(&mut renderer, "a"),
+            "\x1b[1;1Ha\x1b[K\x1b[2;1H\x1b[J"
+        );
+    }
+}
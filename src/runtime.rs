@@ -0,0 +1,749 @@
+use std::{
+    any::Any,
+    collections::{HashMap, VecDeque},
+    io,
+    panic::{self, AssertUnwindSafe},
+    process,
+    sync::{mpsc::Sender, Arc, Mutex},
+    thread,
+};
+
+use crate::{
+    command::{self, CancelToken, ExecDone},
+    scheduler::Scheduler,
+    subscription::Emitter,
+    Command, Error, Event, Message, Result, SignalMessage, TypedApp,
+};
+
+/// What the program should do after a message.
+pub(crate) enum Flow {
+    Continue,
+    /// Print the text above the frame, from `command::println`.
+    Print(String),
+    /// Suspend the process, from `command::suspend`.
+    Suspend,
+    /// Run the process, from `command::exec`, and send the message made from how it finished.
+    Exec(process::Command, ExecDone),
+    /// Quit with the given exit code.
+    Quit(i32),
+}
+
+/// Sent by the event reader thread when reading an event failed.
+pub(crate) struct EventReadFailed(pub io::Error);
+
+/// Sent in place of a command's message when the command panicked.
+pub(crate) struct CommandPanicked(pub String);
+
+impl CommandPanicked {
+    pub(crate) fn from_payload(payload: Box<dyn Any + Send>) -> Self {
+        let msg = match payload.downcast::<String>() {
+            Ok(msg) => *msg,
+            Err(payload) => match payload.downcast::<&str>() {
+                Ok(msg) => msg.to_string(),
+                Err(_) => String::new(),
+            },
+        };
+        Self(msg)
+    }
+}
+
+/// One step of a `command::sequence`, shared by every command that is part of it.
+///
+/// Commands dispatched while handling a message of the step, like the ones from `update` or a nested `batch`,
+/// become part of the step too. Once the last of them has finished and its message has been handled,
+/// the step is dropped, which continues the sequence with the next command.
+pub(crate) struct Step {
+    rest: Mutex<VecDeque<Command>>,
+    parent: Origin,
+    msg_tx: Sender<Message>,
+}
+
+impl Drop for Step {
+    fn drop(&mut self) {
+        let rest = std::mem::take(self.rest.get_mut().unwrap_or_else(|e| e.into_inner()));
+        // the program has already quit if this fails
+        let _ = self.msg_tx.send(Box::new(ContinueSequence {
+            rest,
+            origin: std::mem::take(&mut self.parent),
+        }));
+    }
+}
+
+/// Sent when a step of a sequence has finished.
+struct ContinueSequence {
+    rest: VecDeque<Command>,
+    origin: Origin,
+}
+
+/// Identifies a command dispatched under a key, see `command::keyed`.
+///
+/// It is shared by everything the command leaves behind, like its messages and timers.
+/// Once all of that is gone, the key is forgotten, unless a newer command was dispatched under it in the meantime,
+/// or an older one is still on its way.
+struct KeyTag {
+    key: String,
+    generation: u64,
+    token: CancelToken,
+    msg_tx: Sender<Message>,
+}
+
+impl Drop for KeyTag {
+    fn drop(&mut self) {
+        // the program has already quit if this fails
+        let _ = self.msg_tx.send(Box::new(KeyFinished {
+            key: std::mem::take(&mut self.key),
+            generation: self.generation,
+        }));
+    }
+}
+
+/// Sent once a keyed command has finished, and its messages have been handled.
+struct KeyFinished {
+    key: String,
+    generation: u64,
+}
+
+/// Where a command came from, which decides what happens to the messages it produces.
+#[derive(Clone, Default)]
+pub(crate) struct Origin {
+    /// The step of a sequence the command is part of.
+    step: Option<Arc<Step>>,
+    /// The key the command was dispatched under.
+    key: Option<Arc<KeyTag>>,
+}
+
+/// A message together with the origin of the command that produced it.
+struct Tracked {
+    msg: Message,
+    origin: Origin,
+}
+
+/// Feeds messages to the app, and dispatches the commands it returns.
+///
+/// Built in messages, like the ones produced by `command::quit` and `command::batch`,
+/// are handled here and never reach the app.
+pub(crate) struct Runtime<A: TypedApp> {
+    app: A,
+    scheduler: Box<dyn Scheduler>,
+    msg_tx: Sender<Message>,
+    tokens: Tokens,
+    quit_on_signal: bool,
+    #[cfg(feature = "tokio")]
+    tokio: AsyncRuntime,
+}
+
+impl<A: TypedApp> Runtime<A> {
+    pub(crate) fn new(app: A, scheduler: Box<dyn Scheduler>, msg_tx: Sender<Message>) -> Self {
+        Self {
+            app,
+            scheduler,
+            msg_tx,
+            tokens: Tokens::default(),
+            quit_on_signal: true,
+            #[cfg(feature = "tokio")]
+            tokio: AsyncRuntime::Lazy(None),
+        }
+    }
+
+    /// Whether to quit when the app returns no command for a signal that would have killed it.
+    pub(crate) fn set_quit_on_signal(&mut self, quit: bool) {
+        self.quit_on_signal = quit;
+    }
+
+    /// Runs futures from `command::future` on the given tokio runtime, instead of creating one.
+    #[cfg(feature = "tokio")]
+    pub(crate) fn set_tokio_handle(&mut self, handle: tokio::runtime::Handle) {
+        self.tokio = AsyncRuntime::Handle(handle);
+    }
+
+    pub(crate) fn app(&self) -> &A {
+        &self.app
+    }
+
+    /// Stops everything still running in the background, and gives back the app.
+    pub(crate) fn into_app(self) -> A {
+        self.app
+    }
+
+    /// Dispatches the app's `init` command, if it has one.
+    pub(crate) fn init(&mut self) {
+        if let Some(cmd) = self.app.init() {
+            self.dispatch(cmd.into_untyped(), Origin::default());
+        }
+        self.sync_subscriptions();
+    }
+
+    pub(crate) fn handle(&mut self, msg: Message) -> Result<Flow> {
+        self.handle_in(msg, Origin::default())
+    }
+
+    /// Handles a message that was produced by a command with the given origin.
+    fn handle_in(&mut self, msg: Message, origin: Origin) -> Result<Flow> {
+        #[cfg(feature = "tokio")]
+        if msg.is::<command::FutureMessage>() {
+            let fut = msg.downcast::<command::FutureMessage>().unwrap();
+            self.spawn_future(fut.0, origin)?;
+            return Ok(Flow::Continue);
+        }
+
+        if msg.is::<command::QuitMessage>() {
+            let quit = msg.downcast::<command::QuitMessage>().unwrap();
+            return Ok(Flow::Quit(quit.0));
+        } else if msg.is::<command::SuspendProcessMessage>() {
+            return Ok(Flow::Suspend);
+        } else if msg.is::<command::ExecMessage>() {
+            let exec = *msg.downcast::<command::ExecMessage>().unwrap();
+            let done = exec.done;
+            // the message is part of the step that ran the process
+            let done: ExecDone = Box::new(move |finished| track(done(finished), origin));
+            return Ok(Flow::Exec(exec.cmd, done));
+        } else if msg.is::<command::PrintMessage>() {
+            let print = msg.downcast::<command::PrintMessage>().unwrap();
+            return Ok(Flow::Print(print.0));
+        } else if msg.is::<Tracked>() {
+            let tracked = msg.downcast::<Tracked>().unwrap();
+            if let Some(tag) = &tracked.origin.key {
+                if self
+                    .tokens
+                    .keys
+                    .get(&tag.key)
+                    .map(|(generation, _)| *generation)
+                    != Some(tag.generation)
+                {
+                    // superseded or cancelled
+                    return Ok(Flow::Continue);
+                }
+            }
+            return self.handle_in(tracked.msg, tracked.origin);
+        } else if msg.is::<command::BatchMessage>() {
+            let batch = msg.downcast::<command::BatchMessage>().unwrap();
+            for cmd in batch.0 {
+                self.dispatch(cmd, origin.clone());
+            }
+        } else if msg.is::<command::SequenceMessage>() {
+            let sequence = msg.downcast::<command::SequenceMessage>().unwrap();
+            self.continue_sequence(sequence.0.into(), origin);
+        } else if msg.is::<ContinueSequence>() {
+            let sequence = msg.downcast::<ContinueSequence>().unwrap();
+            self.continue_sequence(sequence.rest, sequence.origin);
+        } else if msg.is::<command::SpawnMessage>() {
+            let spawn = msg.downcast::<command::SpawnMessage>().unwrap();
+            let msg_tx = self.msg_tx.clone();
+            self.scheduler
+                .spawn(Box::new(move || execute(spawn.0, &msg_tx, origin)));
+        } else if msg.is::<command::CancellableMessage>() {
+            let cancellable = msg.downcast::<command::CancellableMessage>().unwrap();
+            let token = self.token_for(&origin);
+            let f = cancellable.0;
+            self.dispatch(Box::new(move || f(token)), origin);
+        } else if msg.is::<command::StreamMessage>() {
+            let stream = msg.downcast::<command::StreamMessage>().unwrap();
+            let emitter =
+                Emitter::new(self.msg_tx.clone(), self.token_for(&origin), origin.clone());
+            let f = stream.0;
+            self.dispatch(Box::new(move || f(emitter)), origin);
+        } else if msg.is::<command::KeyedMessage>() {
+            let command::KeyedMessage { pending, cmd } =
+                *msg.downcast::<command::KeyedMessage>().unwrap();
+            let (key, generation) = (pending.key.clone(), pending.generation);
+            // from here on, the key's entry decides what happens to the command
+            drop(pending);
+            let latest = self
+                .tokens
+                .keys
+                .get(&key)
+                .map(|(generation, _)| *generation);
+            if latest.is_some_and(|latest| latest > generation) {
+                // a newer command with the same key, or a cancel, got here first
+                self.forget_if_settled(&key);
+                return Ok(Flow::Continue);
+            }
+
+            let token = CancelToken::default();
+            let superseded = self
+                .tokens
+                .keys
+                .insert(key.clone(), (generation, token.clone()));
+            if let Some((_, superseded)) = superseded {
+                superseded.cancel();
+            }
+
+            let origin = Origin {
+                step: origin.step,
+                key: Some(Arc::new(KeyTag {
+                    key,
+                    generation,
+                    token,
+                    msg_tx: self.msg_tx.clone(),
+                })),
+            };
+            self.dispatch(cmd, origin);
+        } else if msg.is::<command::CancelMessage>() {
+            let cancel = *msg.downcast::<command::CancelMessage>().unwrap();
+            let latest = self
+                .tokens
+                .keys
+                .get(&cancel.key)
+                .map(|(generation, _)| *generation);
+            if latest.is_none_or(|latest| latest < cancel.generation) {
+                let token = CancelToken::default();
+                token.cancel();
+                if let Some((_, cancelled)) = self
+                    .tokens
+                    .keys
+                    .insert(cancel.key.clone(), (cancel.generation, token))
+                {
+                    cancelled.cancel();
+                }
+                self.forget_if_settled(&cancel.key);
+            }
+        } else if msg.is::<KeyFinished>() {
+            let finished = msg.downcast::<KeyFinished>().unwrap();
+            let latest = self
+                .tokens
+                .keys
+                .get(&finished.key)
+                .map(|(generation, _)| *generation);
+            if latest == Some(finished.generation) {
+                if let Some((_, token)) = self.tokens.keys.get(&finished.key) {
+                    token.cancel();
+                }
+                self.forget_if_settled(&finished.key);
+            }
+        } else if msg.is::<command::TimerMessage>() {
+            let timer = msg.downcast::<command::TimerMessage>().unwrap();
+            let fire = timer.fire;
+            self.scheduler
+                .schedule(timer.delay, Box::new(move |at| track(fire(at), origin)));
+        } else if msg.is::<EventReadFailed>() {
+            let failed = msg.downcast::<EventReadFailed>().unwrap();
+            return Err(Error::EventRead(failed.0));
+        } else if msg.is::<CommandPanicked>() {
+            let panicked = msg.downcast::<CommandPanicked>().unwrap();
+            return Err(Error::CommandPanicked(panicked.0));
+        } else {
+            let mut signal = None;
+            let msg = match msg.downcast::<Event>() {
+                Ok(event) => {
+                    if let Event::Signal(received) = *event {
+                        signal = Some(received);
+                    }
+                    A::Msg::from(*event)
+                }
+                Err(msg) => match command::restore::<A::Msg>(msg) {
+                    Ok(msg) => msg,
+                    // not the app's message type
+                    Err(_) => return Ok(Flow::Continue),
+                },
+            };
+
+            // commands from `update` are part of the same step, but not under the same key
+            match self.app.update(msg) {
+                Some(cmd) => {
+                    let origin = Origin {
+                        step: origin.step,
+                        key: None,
+                    };
+                    self.dispatch(cmd.into_untyped(), origin);
+                }
+                None => {
+                    // the app ignored a signal that would have killed it
+                    let code = signal
+                        .filter(|_| self.quit_on_signal)
+                        .and_then(SignalMessage::exit_code);
+                    if let Some(code) = code {
+                        return Ok(Flow::Quit(code));
+                    }
+                }
+            }
+            self.sync_subscriptions();
+        }
+
+        Ok(Flow::Continue)
+    }
+
+    /// The token that stops a command from the given origin: the one of its key, or else the one for quitting.
+    /// Forgets a key that was cancelled, or whose command has finished, once no keyed command
+    /// with the same key that was created before it is still on its way.
+    /// Until then, the key is kept, so that such a command is dropped once it gets here, instead of starting.
+    fn forget_if_settled(&mut self, key: &str) {
+        if let Some((generation, token)) = self.tokens.keys.get(key) {
+            if token.is_cancelled() && !command::is_pending_before(key, *generation) {
+                self.tokens.keys.remove(key);
+            }
+        }
+    }
+
+    fn token_for(&self, origin: &Origin) -> CancelToken {
+        match &origin.key {
+            Some(tag) => tag.token.clone(),
+            None => self.tokens.quit.clone(),
+        }
+    }
+
+    fn dispatch(&self, cmd: Command, origin: Origin) {
+        let msg_tx = self.msg_tx.clone();
+        self.scheduler
+            .execute(Box::new(move || execute(cmd, &msg_tx, origin)));
+    }
+
+    /// Starts the subscriptions the app newly asks for, and stops the ones it no longer does.
+    fn sync_subscriptions(&mut self) {
+        let mut wanted = HashMap::new();
+        for subscription in self.app.subscriptions() {
+            let (id, start) = subscription.into_untyped().into_parts();
+            wanted.entry(id).or_insert(start);
+        }
+
+        self.tokens.subscriptions.retain(|id, token| {
+            let keep = wanted.contains_key(id);
+            if !keep {
+                token.cancel();
+            }
+            keep
+        });
+
+        for (id, start) in wanted {
+            if self.tokens.subscriptions.contains_key(&id) {
+                continue;
+            }
+
+            let token = CancelToken::default();
+            let emitter = Emitter::new(self.msg_tx.clone(), token.clone(), Origin::default());
+            let msg_tx = self.msg_tx.clone();
+            thread::spawn(move || {
+                if let Err(payload) = panic::catch_unwind(AssertUnwindSafe(|| start(emitter))) {
+                    let _ = msg_tx.send(Box::new(CommandPanicked::from_payload(payload)));
+                }
+            });
+            self.tokens.subscriptions.insert(id, token);
+        }
+    }
+
+    /// Dispatches the next command of a sequence as a new step.
+    /// If there are none left, the sequence is finished, which in turn finishes its part of the parent step.
+    fn continue_sequence(&self, mut rest: VecDeque<Command>, parent: Origin) {
+        if let Some(cmd) = rest.pop_front() {
+            let key = parent.key.clone();
+            let step = Arc::new(Step {
+                rest: Mutex::new(rest),
+                parent,
+                msg_tx: self.msg_tx.clone(),
+            });
+            self.dispatch(
+                cmd,
+                Origin {
+                    step: Some(step),
+                    key,
+                },
+            );
+        }
+    }
+
+    #[cfg(feature = "tokio")]
+    fn spawn_future(
+        &mut self,
+        fut: std::pin::Pin<Box<dyn std::future::Future<Output = Option<Message>> + Send>>,
+        origin: Origin,
+    ) -> Result<()> {
+        let handle = self.tokio.handle()?;
+        let task = handle.spawn(fut);
+        let msg_tx = self.msg_tx.clone();
+        // a second task to catch the first one panicking
+        handle.spawn(async move {
+            let msg: Message = match task.await {
+                Ok(Some(msg)) => track(msg, origin),
+                Ok(None) => return,
+                Err(err) if err.is_panic() => {
+                    Box::new(CommandPanicked::from_payload(err.into_panic()))
+                }
+                Err(_) => return,
+            };
+            let _ = msg_tx.send(msg);
+        });
+        Ok(())
+    }
+}
+
+/// The tokens of everything that runs in the background. All of them are cancelled when dropped.
+#[derive(Default)]
+struct Tokens {
+    /// Cancelled when the program quits.
+    quit: CancelToken,
+    /// The latest generation and token of every key.
+    /// Keys whose command has finished or was cancelled have a cancelled token, see `Runtime::forget_if_settled`.
+    keys: HashMap<String, (u64, CancelToken)>,
+    /// The tokens that stop each running subscription, by id.
+    subscriptions: HashMap<String, CancelToken>,
+}
+
+impl Drop for Tokens {
+    fn drop(&mut self) {
+        self.quit.cancel();
+        for (_, token) in self.keys.values() {
+            token.cancel();
+        }
+        for token in self.subscriptions.values() {
+            token.cancel();
+        }
+    }
+}
+
+/// The tokio runtime futures are run on.
+#[cfg(feature = "tokio")]
+enum AsyncRuntime {
+    /// Created the first time it is needed.
+    Lazy(Option<tokio::runtime::Runtime>),
+    Handle(tokio::runtime::Handle),
+}
+
+#[cfg(feature = "tokio")]
+impl AsyncRuntime {
+    fn handle(&mut self) -> Result<tokio::runtime::Handle> {
+        match self {
+            AsyncRuntime::Lazy(Some(runtime)) => Ok(runtime.handle().clone()),
+            AsyncRuntime::Lazy(runtime) => {
+                let created = tokio::runtime::Builder::new_multi_thread()
+                    .enable_all()
+                    .build()?;
+                Ok(runtime.insert(created).handle().clone())
+            }
+            AsyncRuntime::Handle(handle) => Ok(handle.clone()),
+        }
+    }
+}
+
+#[cfg(feature = "tokio")]
+impl Drop for AsyncRuntime {
+    fn drop(&mut self) {
+        // don't wait for futures that are still running
+        if let AsyncRuntime::Lazy(runtime) = self {
+            if let Some(runtime) = runtime.take() {
+                runtime.shutdown_background();
+            }
+        }
+    }
+}
+
+/// Runs a command and sends its message, or a `CommandPanicked` if it panicked.
+fn execute(cmd: Command, msg_tx: &Sender<Message>, origin: Origin) {
+    let msg = match panic::catch_unwind(AssertUnwindSafe(cmd)) {
+        Ok(Some(msg)) => track(msg, origin),
+        Ok(None) => return,
+        Err(payload) => Box::new(CommandPanicked::from_payload(payload)),
+    };
+    // the program has already quit if this fails
+    let _ = msg_tx.send(msg);
+}
+
+/// Attaches the origin to the message, unless there is nothing to attach.
+pub(crate) fn track(msg: Message, origin: Origin) -> Message {
+    if origin.step.is_none() && origin.key.is_none() {
+        msg
+    } else {
+        Box::new(Tracked { msg, origin })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{sync::mpsc, time::Duration};
+
+    use super::*;
+    use crate::{scheduler::Deterministic, testing::TestProgram, App};
+
+    struct Log(&'static str);
+
+    /// Makes the app return the command from `update`.
+    struct Run(Command);
+
+    #[derive(Default)]
+    struct Recorder {
+        log: Vec<&'static str>,
+    }
+
+    impl App for Recorder {
+        fn update(&mut self, msg: Message) -> Option<Command> {
+            match msg.downcast::<Run>() {
+                Ok(run) => Some(run.0),
+                Err(msg) => {
+                    if let Some(log) = msg.downcast_ref::<Log>() {
+                        self.log.push(log.0);
+                    }
+                    None
+                }
+            }
+        }
+
+        fn view(&self) -> String {
+            self.log.join(" ")
+        }
+    }
+
+    fn log(text: &'static str) -> Command {
+        Box::new(move || Some(Box::new(Log(text))))
+    }
+
+    fn tick(seconds: u64, text: &'static str) -> Command {
+        command::tick(Duration::from_secs(seconds), move |_| Box::new(Log(text)))
+    }
+
+    fn run(program: &mut TestProgram<Recorder>, cmd: Command) {
+        program.send(Box::new(Run(cmd)));
+        program.run_until_idle();
+    }
+
+    #[test]
+    fn sequences_run_in_order() {
+        let mut program = TestProgram::stepped(Recorder::default(), 10, 10);
+        run(
+            &mut program,
+            command::sequence(vec![
+                tick(2, "a"),
+                command::batch(vec![tick(1, "b"), log("c")]),
+                command::sequence(vec![log("d"), tick(1, "e")]),
+                log("f"),
+            ]),
+        );
+        // everything waits for the first timer
+        assert!(program.app().log.is_empty());
+
+        program.advance(Duration::from_secs(2));
+        program.run_until_idle();
+        assert_eq!(program.app().log, ["a", "c"]);
+
+        // the batch is only done once its timer has fired too
+        program.advance(Duration::from_secs(1));
+        program.run_until_idle();
+        assert_eq!(program.app().log, ["a", "c", "b", "d"]);
+
+        program.advance(Duration::from_secs(1));
+        program.run_until_idle();
+        assert_eq!(program.app().log, ["a", "c", "b", "d", "e", "f"]);
+    }
+
+    #[test]
+    fn keyed_commands_supersede_each_other() {
+        let mut program = TestProgram::stepped(Recorder::default(), 10, 10);
+        program.send(Box::new(Run(command::keyed("search", log("first")))));
+        program.send(Box::new(Run(command::keyed("search", log("second")))));
+        program.send(Box::new(Run(command::keyed("other", log("other")))));
+        program.run_until_idle();
+        assert_eq!(program.app().log, ["second", "other"]);
+
+        run(&mut program, command::keyed("timer", tick(1, "late")));
+        run(&mut program, command::keyed("timer", tick(2, "latest")));
+        program.advance(Duration::from_secs(2));
+        program.run_until_idle();
+        assert_eq!(program.app().log, ["second", "other", "latest"]);
+    }
+
+    #[test]
+    fn cancelled_commands_are_dropped() {
+        let mut program = TestProgram::stepped(Recorder::default(), 10, 10);
+        let token = Arc::new(Mutex::new(None));
+        let keep = token.clone();
+        run(
+            &mut program,
+            command::keyed(
+                "work",
+                command::batch(vec![
+                    tick(1, "tick"),
+                    command::cancellable(move |token| {
+                        *keep.lock().unwrap() = Some(token);
+                        None
+                    }),
+                ]),
+            ),
+        );
+        let token = token.lock().unwrap().take().unwrap();
+        assert!(!token.is_cancelled());
+
+        run(&mut program, command::cancel("work"));
+        assert!(token.is_cancelled());
+        program.advance(Duration::from_secs(1));
+        program.run_until_idle();
+        assert!(program.app().log.is_empty());
+
+        // cancelling again, or a key that was never used, does nothing
+        run(&mut program, command::cancel("work"));
+        run(&mut program, command::cancel("unknown"));
+        run(&mut program, command::keyed("work", log("again")));
+        assert_eq!(program.app().log, ["again"]);
+    }
+
+    #[test]
+    fn keyed_commands_take_effect_in_the_order_they_were_created() {
+        for _ in 0..10 {
+            let mut program = TestProgram::new(Recorder::default(), 10, 10);
+            // the batch makes the older command reach the runtime later
+            program.send(Box::new(Run(command::batch(vec![command::keyed(
+                "search",
+                log("old"),
+            )]))));
+            program.send(Box::new(Run(command::keyed("search", log("new")))));
+            assert!(program.wait_until(Duration::from_secs(5), |app| app.log.contains(&"new")));
+            // the old result may only get there if it was done before the new command started
+            program.wait_until(Duration::from_millis(20), |_| false);
+            assert_eq!(program.app().log.last(), Some(&"new"));
+            let mut program = TestProgram::new(Recorder::default(), 10, 10);
+
+            program.send(Box::new(Run(command::batch(vec![command::keyed(
+                "work",
+                // takes long enough for the cancel to get there first, whichever order they start in
+                command::cancellable(|token| {
+                    token.wait_timeout(Duration::from_millis(50));
+                    Some(Box::new(Log("cancelled")))
+                }),
+            )]))));
+            program.send(Box::new(Run(command::cancel("work"))));
+            assert!(!program.wait_until(Duration::from_millis(100), |app| !app.log.is_empty()));
+            program.send(Box::new(Run(command::keyed("work", log("again")))));
+            assert!(program.wait_until(Duration::from_secs(5), |app| !app.log.is_empty()));
+            assert_eq!(program.app().log, ["again"]);
+        }
+    }
+
+    #[test]
+    fn keys_are_forgotten_once_finished() {
+        let (msg_tx, msg_rx) = mpsc::channel();
+        let scheduler = Deterministic::new(msg_tx.clone());
+        let mut runtime = Runtime::new(Recorder::default(), Box::new(scheduler.clone()), msg_tx);
+        let run_until_idle = |runtime: &mut Runtime<Recorder>| loop {
+            if let Ok(msg) = msg_rx.try_recv() {
+                runtime.handle(msg).unwrap();
+            } else if !scheduler.run_next() && !scheduler.fire_next(None) {
+                break;
+            }
+        };
+
+        runtime
+            .handle(Box::new(Run(command::keyed("a", log("a")))))
+            .unwrap();
+        runtime
+            .handle(Box::new(Run(command::keyed("b", tick(1, "b")))))
+            .unwrap();
+        runtime
+            .handle(Box::new(Run(command::keyed("b", log("c")))))
+            .unwrap();
+        run_until_idle(&mut runtime);
+        assert_eq!(runtime.app().log, ["a", "c"]);
+        assert!(runtime.tokens.keys.is_empty());
+
+        // a command created earlier is still to come, so the key is kept until it got here and was dropped
+        let old = command::keyed("d", log("old"));
+        runtime
+            .handle(Box::new(Run(command::keyed("d", log("new")))))
+            .unwrap();
+        run_until_idle(&mut runtime);
+        assert!(runtime.tokens.keys.contains_key("d"));
+        runtime.handle(Box::new(Run(old))).unwrap();
+        run_until_idle(&mut runtime);
+        assert_eq!(runtime.app().log, ["a", "c", "new"]);
+        assert!(runtime.tokens.keys.is_empty());
+
+        runtime.handle(Box::new(Run(command::cancel("e")))).unwrap();
+        run_until_idle(&mut runtime);
+        assert!(runtime.tokens.keys.is_empty());
+    }
+}
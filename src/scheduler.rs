@@ -0,0 +1,163 @@
+use std::{
+    collections::VecDeque,
+    sync::{mpsc::Sender, Arc, Mutex, MutexGuard},
+    time::Instant,
+};
+
+use crate::{
+    command::Delay,
+    pool::ThreadPool,
+    timer::{self, Fire, Timer, TimerQueue},
+    Message,
+};
+
+pub(crate) type Job = Box<dyn FnOnce() + Send + 'static>;
+
+/// Decides where and when commands are executed, and when timers fire.
+pub(crate) trait Scheduler {
+    /// Executes a command.
+    fn execute(&self, job: Job);
+
+    /// Executes a command that may block for a long time, from `command::spawn`.
+    fn spawn(&self, job: Job);
+
+    /// Sends the message made by `fire` once the delay has passed.
+    fn schedule(&mut self, delay: Delay, fire: Fire);
+}
+
+/// The scheduler of a real program: commands are executed on a pool of worker threads,
+/// and timers fire by the system clock, from a thread of their own.
+pub(crate) struct Threaded {
+    pool: ThreadPool,
+    timer: Timer,
+}
+
+impl Threaded {
+    pub(crate) fn new(pool: ThreadPool, msg_tx: Sender<Message>) -> Self {
+        Self {
+            pool,
+            timer: Timer::new(msg_tx),
+        }
+    }
+}
+
+impl Scheduler for Threaded {
+    fn execute(&self, job: Job) {
+        self.pool.execute(job);
+    }
+
+    fn spawn(&self, job: Job) {
+        self.pool.spawn(job);
+    }
+
+    fn schedule(&mut self, delay: Delay, fire: Fire) {
+        let now = Instant::now();
+        let deadline = match delay {
+            Delay::After(duration) => now.checked_add(duration),
+            Delay::Boundary(interval) => now.checked_add(timer::until_boundary(interval)),
+        };
+        self.timer.schedule(deadline, fire);
+    }
+}
+
+/// A scheduler for tests, which only executes commands when asked to, one at a time, on the thread asking.
+///
+/// Timers fire by a virtual clock, which only moves when it is advanced.
+/// It starts at zero, so `command::every` lines up with multiples of its duration since the start.
+///
+/// Clones share the same queue and clock, so one can be kept to drive the scheduler the runtime owns.
+#[derive(Clone)]
+pub(crate) struct Deterministic {
+    shared: Arc<Mutex<State>>,
+    msg_tx: Sender<Message>,
+}
+
+struct State {
+    jobs: VecDeque<Job>,
+    timers: TimerQueue,
+    start: Instant,
+    now: Instant,
+}
+
+impl Deterministic {
+    pub(crate) fn new(msg_tx: Sender<Message>) -> Self {
+        let start = Instant::now();
+        Self {
+            shared: Arc::new(Mutex::new(State {
+                jobs: VecDeque::new(),
+                timers: TimerQueue::default(),
+                start,
+                now: start,
+            })),
+            msg_tx,
+        }
+    }
+
+    /// Executes the next queued command on the current thread. Returns `false` if there was none.
+    pub(crate) fn run_next(&self) -> bool {
+        let job = self.lock().jobs.pop_front();
+        match job {
+            Some(job) => {
+                job();
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Moves the clock to the earliest timer due no later than `until`, and fires it.
+    /// If there is none, moves the clock to `until` instead, and returns `false`.
+    /// Without `until`, which is for moving the clock further than it can go, any timer is due.
+    pub(crate) fn fire_next(&self, until: Option<Instant>) -> bool {
+        let mut state = self.lock();
+        let deadline = match state.timers.next_deadline() {
+            Some(deadline) if until.is_none_or(|until| deadline <= until) => deadline,
+            _ => {
+                if let Some(until) = until {
+                    state.now = state.now.max(until);
+                }
+                return false;
+            }
+        };
+
+        state.now = deadline;
+        let fire = state.timers.pop_due(deadline);
+        drop(state);
+        if let Some(fire) = fire {
+            let _ = self.msg_tx.send(timer::fire_at(fire, deadline));
+        }
+        true
+    }
+
+    /// The current time on the virtual clock.
+    pub(crate) fn now(&self) -> Instant {
+        self.lock().now
+    }
+
+    fn lock(&self) -> MutexGuard<'_, State> {
+        // jobs never run while the lock is held, so it can't be poisoned by a panicking job
+        self.shared.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+impl Scheduler for Deterministic {
+    fn execute(&self, job: Job) {
+        self.lock().jobs.push_back(job);
+    }
+
+    fn spawn(&self, job: Job) {
+        self.execute(job);
+    }
+
+    fn schedule(&mut self, delay: Delay, fire: Fire) {
+        let mut state = self.lock();
+        let now = state.now;
+        let deadline = match delay {
+            Delay::After(duration) => now.checked_add(duration),
+            Delay::Boundary(interval) => {
+                now.checked_add(timer::until_next_multiple(interval, now - state.start))
+            }
+        };
+        state.timers.push(deadline, fire);
+    }
+}
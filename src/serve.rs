@@ -0,0 +1,542 @@
+//! Serving an app over a socket, to many clients at once.
+//!
+//! `run` accepts connections on a listener, and runs a program of its own for each of them,
+//! reading events from and rendering to the connection. Once a client disconnects, its program is dropped
+//! along with its commands and subscriptions. Once a program quits, its connection is closed.
+//!
+//! Over TCP, clients are expected to be telnet clients, like `telnet localhost 2323`.
+//! The server asks them for their terminal's size, and delivers it and every change to it as a resize event.
+//! Over unix sockets, the bytes are taken as they are, so a client like `socat -,raw,echo=0 UNIX-CONNECT:path`
+//! works, but the size of its terminal isn't known.
+//!
+//! Programs run in a session don't receive the process's signals, and `command::suspend` does nothing for them.
+//! `command::exec` doesn't run the process, since it would take over the server's terminal instead of the client's,
+//! and finishes with an `ExecFinished` whose status is an error of kind `Unsupported` instead.
+//!
+//! # Example
+//!
+//! ```no_run
+//! # use rustea::{App, Command, Message};
+//! # #[derive(Default)]
+//! # struct Dashboard;
+//! # impl App for Dashboard {
+//! #     fn update(&mut self, _msg: Message) -> Option<Command> { None }
+//! #     fn view(&self) -> String { String::new() }
+//! # }
+//! use std::net::TcpListener;
+//!
+//! use rustea::{serve, Program};
+//!
+//! let listener = TcpListener::bind("127.0.0.1:2323").unwrap();
+//! serve::run(listener, || Program::new(Dashboard::default())).unwrap();
+//! ```
+
+#[cfg(unix)]
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::{
+    collections::VecDeque,
+    io::{self, ErrorKind, Read, Write},
+    net::{Shutdown, TcpListener, TcpStream},
+    sync::{
+        mpsc::{Receiver, RecvTimeoutError},
+        Arc,
+    },
+    thread,
+    time::{Duration, Instant},
+};
+
+use crate::{
+    input::{self, EventSource, Parser},
+    Event, Program, Result, TypedApp,
+};
+
+/// How long a session waits for a telnet client to report its terminal's size, before the program starts anyway.
+const NEGOTIATION_TIMEOUT: Duration = Duration::from_millis(500);
+
+/// How long to wait before accepting again, after running out of file descriptors.
+const ACCEPT_RETRY_DELAY: Duration = Duration::from_millis(100);
+
+/// Something that accepts connections to serve programs over.
+/// Implemented for `TcpListener`, and `UnixListener` on unix.
+pub trait Listener {
+    /// The connections it accepts.
+    type Connection: Connection;
+
+    /// Waits for the next connection.
+    fn accept_connection(&self) -> io::Result<Self::Connection>;
+}
+
+/// A connection a program can be served over.
+pub trait Connection: Read + Write + Send + Sized + 'static {
+    /// Whether the client is expected to speak telnet. Defaults to `false`.
+    const TELNET: bool = false;
+
+    /// Another handle to the same connection, so it can be read from and written to at once.
+    fn duplicate(&self) -> io::Result<Self>;
+
+    /// Closes the connection for every handle to it.
+    fn close(&self) -> io::Result<()>;
+}
+
+impl Listener for TcpListener {
+    type Connection = TcpStream;
+
+    fn accept_connection(&self) -> io::Result<TcpStream> {
+        self.accept().map(|(stream, _)| stream)
+    }
+}
+
+impl Connection for TcpStream {
+    const TELNET: bool = true;
+
+    fn duplicate(&self) -> io::Result<Self> {
+        self.try_clone()
+    }
+
+    fn close(&self) -> io::Result<()> {
+        self.shutdown(Shutdown::Both)
+    }
+}
+
+#[cfg(unix)]
+impl Listener for UnixListener {
+    type Connection = UnixStream;
+
+    fn accept_connection(&self) -> io::Result<UnixStream> {
+        self.accept().map(|(stream, _)| stream)
+    }
+}
+
+#[cfg(unix)]
+impl Connection for UnixStream {
+    fn duplicate(&self) -> io::Result<Self> {
+        self.try_clone()
+    }
+
+    fn close(&self) -> io::Result<()> {
+        self.shutdown(Shutdown::Both)
+    }
+}
+
+/// Accepts connections on the listener forever, running the program made by `factory` for each of them
+/// on a thread of its own.
+///
+/// The program's input and output are replaced by the connection, but everything else it was configured with,
+/// like `Program::mouse`, is kept. It starts out of raw mode, which only concerns the process's own terminal.
+///
+/// Returns an error if the listener fails. Connections that fail while being accepted are skipped,
+/// and while the process is out of file descriptors, accepting waits until some are freed up.
+/// Errors in sessions only end that session.
+pub fn run<L, A, F>(listener: L, factory: F) -> io::Result<()>
+where
+    L: Listener,
+    A: TypedApp,
+    F: Fn() -> Program<A> + Send + Sync + 'static,
+{
+    let factory = Arc::new(factory);
+    loop {
+        let connection = match listener.accept_connection() {
+            Ok(connection) => connection,
+            Err(err) if out_of_resources(&err) => {
+                // give sessions a chance to end and free some up, instead of spinning
+                thread::sleep(ACCEPT_RETRY_DELAY);
+                continue;
+            }
+            Err(err) if failed_connection(&err) => continue,
+            Err(err) => return Err(err),
+        };
+        let factory = factory.clone();
+        thread::spawn(move || {
+            // there is no one left to tell about a session going wrong, so it is simply over
+            let _ = session(connection, &*factory);
+        });
+    }
+}
+
+/// Whether accepting failed because of the connection being accepted, and the next one may well work.
+fn failed_connection(err: &io::Error) -> bool {
+    matches!(
+        err.kind(),
+        ErrorKind::ConnectionAborted
+            | ErrorKind::ConnectionReset
+            | ErrorKind::Interrupted
+            | ErrorKind::WouldBlock
+            | ErrorKind::TimedOut
+    )
+}
+
+/// Whether accepting failed because the process or the system ran out of file descriptors or memory for now.
+fn out_of_resources(err: &io::Error) -> bool {
+    // EMFILE and ENFILE, which have no `ErrorKind` of their own
+    #[cfg(unix)]
+    if matches!(err.raw_os_error(), Some(23 | 24)) {
+        return true;
+    }
+    err.kind() == ErrorKind::OutOfMemory
+}
+
+fn session<C: Connection, A: TypedApp>(
+    connection: C,
+    factory: &dyn Fn() -> Program<A>,
+) -> Result<()> {
+    let _closing = Closing(&connection);
+    let mut output = connection.duplicate()?;
+    let mut input = SessionInput::new(connection.duplicate()?, C::TELNET);
+    if C::TELNET {
+        // the client echoes nothing and sends every key right away, and reports the size of its window
+        output.write_all(&[
+            IAC,
+            WILL,
+            OPTION_ECHO,
+            IAC,
+            WILL,
+            OPTION_SUPPRESS_GO_AHEAD,
+            IAC,
+            DO,
+            OPTION_NAWS,
+        ])?;
+        output.flush()?;
+        input.wait_for_size(NEGOTIATION_TIMEOUT);
+    }
+
+    factory()
+        .detached()
+        .input(input)
+        .output(output)
+        .run()
+        .map(drop)
+}
+
+/// Closes the connection once the session is over, however it ends.
+/// The thread reading from it holds a handle of its own, so the connection would otherwise stay open
+/// when the app panics, leaving the client waiting.
+struct Closing<'a, C: Connection>(&'a C);
+
+impl<C: Connection> Drop for Closing<'_, C> {
+    fn drop(&mut self) {
+        // the client may well be gone already
+        let _ = self.0.close();
+    }
+}
+
+const IAC: u8 = 255;
+const DONT: u8 = 254;
+const DO: u8 = 253;
+const WONT: u8 = 252;
+const WILL: u8 = 251;
+const SB: u8 = 250;
+const SE: u8 = 240;
+const OPTION_ECHO: u8 = 1;
+const OPTION_SUPPRESS_GO_AHEAD: u8 = 3;
+const OPTION_NAWS: u8 = 31;
+
+/// How much of a subnegotiation is kept. Window sizes take 5 bytes, and anything longer is of no interest,
+/// so the rest is dropped, instead of letting a client make the server allocate without bound.
+const MAX_SUB_LEN: usize = 64;
+
+/// Reads a session's events from its connection, leaving out telnet commands,
+/// and turning the client's window size reports into resize events.
+struct SessionInput {
+    chunks: Receiver<io::Result<Vec<u8>>>,
+    telnet: Option<Telnet>,
+    parser: Parser,
+    events: VecDeque<Event>,
+    size: Option<(u16, u16)>,
+}
+
+impl SessionInput {
+    fn new(connection: impl Read + Send + 'static, telnet: bool) -> Self {
+        Self {
+            chunks: input::read_chunks(connection),
+            telnet: telnet.then(Telnet::default),
+            parser: Parser::default(),
+            events: VecDeque::new(),
+            size: None,
+        }
+    }
+
+    /// Reads from the connection until the client has reported its size, or the timeout passes.
+    /// Whatever else was sent in the meantime is kept for later.
+    fn wait_for_size(&mut self, timeout: Duration) {
+        let deadline = Instant::now() + timeout;
+        while self.size.is_none() {
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            match self.chunks.recv_timeout(remaining) {
+                Ok(Ok(chunk)) => self.receive(&chunk),
+                // a failed connection closes the channel, which `read` comes across later
+                _ => return,
+            }
+        }
+    }
+
+    fn receive(&mut self, chunk: &[u8]) {
+        let telnet = match &mut self.telnet {
+            Some(telnet) => telnet,
+            None => return self.events.extend(self.parser.parse(chunk)),
+        };
+
+        let mut data = Vec::new();
+        for &byte in chunk {
+            match telnet.feed(byte) {
+                Some(Received::Data(byte)) => data.push(byte),
+                Some(Received::Size(width, height)) => {
+                    self.events.extend(self.parser.parse(&data));
+                    data.clear();
+                    self.size = Some((width, height));
+                    self.events.push_back(Event::Resize(width, height));
+                }
+                None => (),
+            }
+        }
+        self.events.extend(self.parser.parse(&data));
+    }
+}
+
+impl EventSource for SessionInput {
+    fn read(&mut self, timeout: Duration) -> io::Result<Option<Event>> {
+        if let Some(event) = self.events.pop_front() {
+            return Ok(Some(event));
+        }
+
+        match self.chunks.recv_timeout(timeout) {
+            Ok(chunk) => {
+                self.receive(&chunk?);
+                Ok(self.events.pop_front())
+            }
+            Err(RecvTimeoutError::Timeout) => Ok(None),
+            Err(RecvTimeoutError::Disconnected) => Err(ErrorKind::UnexpectedEof.into()),
+        }
+    }
+
+    fn size(&self) -> Option<(u16, u16)> {
+        self.size
+    }
+}
+
+/// What a byte from a telnet client amounts to.
+#[derive(Debug, PartialEq, Eq)]
+enum Received {
+    Data(u8),
+    Size(u16, u16),
+}
+
+/// Separates the data a telnet client sends from its commands, one byte at a time.
+#[derive(Default)]
+struct Telnet {
+    state: TelnetState,
+    /// The contents of the current subnegotiation.
+    sub: Vec<u8>,
+}
+
+#[derive(Default)]
+enum TelnetState {
+    #[default]
+    Data,
+    /// After `IAC`.
+    Command,
+    /// After `IAC WILL`, `WONT`, `DO` or `DONT`, which are followed by an option.
+    Option,
+    /// After `IAC SB`.
+    Sub,
+    /// After `IAC` within a subnegotiation.
+    SubCommand,
+}
+
+impl Telnet {
+    fn feed(&mut self, byte: u8) -> Option<Received> {
+        match self.state {
+            TelnetState::Data if byte == IAC => self.state = TelnetState::Command,
+            TelnetState::Data => return Some(Received::Data(byte)),
+            TelnetState::Command => match byte {
+                // an escaped 255, which is data after all
+                IAC => {
+                    self.state = TelnetState::Data;
+                    return Some(Received::Data(IAC));
+                }
+                WILL | WONT | DO | DONT => self.state = TelnetState::Option,
+                SB => {
+                    self.sub.clear();
+                    self.state = TelnetState::Sub;
+                }
+                _ => self.state = TelnetState::Data,
+            },
+            TelnetState::Option => self.state = TelnetState::Data,
+            TelnetState::Sub if byte == IAC => self.state = TelnetState::SubCommand,
+            TelnetState::Sub => self.push_sub(byte),
+            TelnetState::SubCommand if byte == IAC => {
+                self.push_sub(IAC);
+                self.state = TelnetState::Sub;
+            }
+            TelnetState::SubCommand => {
+                self.state = TelnetState::Data;
+                // a size of zero means the client doesn't know it
+                if let [OPTION_NAWS, w1, w0, h1, h0] = self.sub[..] {
+                    let (width, height) =
+                        (u16::from_be_bytes([w1, w0]), u16::from_be_bytes([h1, h0]));
+                    if byte == SE && width > 0 && height > 0 {
+                        return Some(Received::Size(width, height));
+                    }
+                }
+            }
+        }
+        None
+    }
+
+    fn push_sub(&mut self, byte: u8) {
+        if self.sub.len() < MAX_SUB_LEN {
+            self.sub.push(byte);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+
+    use super::*;
+
+    fn feed(telnet: &mut Telnet, bytes: &[u8]) -> Vec<Received> {
+        bytes.iter().filter_map(|&byte| telnet.feed(byte)).collect()
+    }
+
+    fn key(c: char) -> Event {
+        Event::Key(KeyEvent::new(KeyCode::Char(c), KeyModifiers::NONE))
+    }
+
+    #[test]
+    fn commands_are_left_out() {
+        let mut telnet = Telnet::default();
+        assert_eq!(
+            feed(
+                &mut telnet,
+                &[
+                    b'a',
+                    IAC,
+                    DO,
+                    OPTION_ECHO,
+                    b'b',
+                    IAC,
+                    WILL,
+                    OPTION_NAWS,
+                    IAC,
+                    241,
+                    b'c'
+                ]
+            ),
+            vec![
+                Received::Data(b'a'),
+                Received::Data(b'b'),
+                Received::Data(b'c')
+            ]
+        );
+        // an escaped 255, and an option that happens to be 255
+        assert_eq!(
+            feed(&mut telnet, &[IAC, IAC, IAC, WONT, IAC, b'd']),
+            vec![Received::Data(IAC), Received::Data(b'd')]
+        );
+    }
+
+    #[test]
+    fn window_size() {
+        let mut telnet = Telnet::default();
+        assert_eq!(
+            feed(
+                &mut telnet,
+                &[IAC, SB, OPTION_NAWS, 0, 80, 0, 24, IAC, SE, b'a']
+            ),
+            vec![Received::Size(80, 24), Received::Data(b'a')]
+        );
+        // sizes containing a 255 have it escaped
+        assert_eq!(
+            feed(
+                &mut telnet,
+                &[
+                    IAC,
+                    SB,
+                    OPTION_NAWS,
+                    0,
+                    IAC,
+                    IAC,
+                    IAC,
+                    IAC,
+                    IAC,
+                    IAC,
+                    IAC,
+                    SE
+                ]
+            ),
+            vec![Received::Size(255, 65535)]
+        );
+        // unknown sizes, and other subnegotiations, are left out
+        assert_eq!(
+            feed(&mut telnet, &[IAC, SB, OPTION_NAWS, 0, 0, 0, 0, IAC, SE]),
+            vec![]
+        );
+        assert_eq!(
+            feed(
+                &mut telnet,
+                &[IAC, SB, 24, 0, b'x', b't', b'e', b'r', b'm', IAC, SE, b'b']
+            ),
+            vec![Received::Data(b'b')]
+        );
+    }
+
+    #[test]
+    fn long_subnegotiations_are_cut_off() {
+        let mut telnet = Telnet::default();
+        feed(&mut telnet, &[IAC, SB, 24]);
+        for _ in 0..1000 {
+            feed(&mut telnet, &[b'x', IAC, IAC]);
+        }
+        assert_eq!(telnet.sub.len(), MAX_SUB_LEN);
+        assert_eq!(
+            feed(
+                &mut telnet,
+                &[IAC, SE, b'a', IAC, SB, OPTION_NAWS, 0, 80, 0, 24, IAC, SE]
+            ),
+            vec![Received::Data(b'a'), Received::Size(80, 24)]
+        );
+    }
+
+    #[test]
+    fn session_input() {
+        let mut input = SessionInput::new(io::empty(), true);
+        input.receive(&[b'a', IAC, SB, OPTION_NAWS, 0, 100, 0]);
+        input.receive(&[40, IAC, SE, b'\r', 0, b'b']);
+        assert_eq!(input.size(), Some((100, 40)));
+        assert_eq!(
+            Vec::from(input.events),
+            vec![
+                key('a'),
+                Event::Resize(100, 40),
+                Event::Key(KeyEvent::new(KeyCode::Enter, KeyModifiers::NONE)),
+                key('b'),
+            ]
+        );
+
+        // without telnet, the bytes are taken as they are
+        let mut input = SessionInput::new(io::empty(), false);
+        input.receive(&[b'a', IAC, SB]);
+        assert_eq!(input.size(), None);
+        assert_eq!(Vec::from(input.events), vec![key('a')]);
+    }
+
+    #[test]
+    fn waiting_for_the_size_keeps_what_came_before() {
+        let bytes = vec![b'a', IAC, SB, OPTION_NAWS, 0, 80, 0, 24, IAC, SE, b'b'];
+        let mut input = SessionInput::new(Cursor::new(bytes), true);
+        input.wait_for_size(Duration::from_secs(5));
+        assert_eq!(input.size(), Some((80, 24)));
+
+        let timeout = Duration::from_secs(5);
+        assert_eq!(input.read(timeout).unwrap(), Some(key('a')));
+        assert_eq!(input.read(timeout).unwrap(), Some(Event::Resize(80, 24)));
+        assert_eq!(input.read(timeout).unwrap(), Some(key('b')));
+        assert_eq!(
+            input.read(timeout).unwrap_err().kind(),
+            ErrorKind::UnexpectedEof
+        );
+    }
+}
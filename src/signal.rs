@@ -0,0 +1,143 @@
+use std::{
+    io::Result,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        mpsc::Sender,
+        Arc, Mutex, MutexGuard,
+    },
+    thread,
+};
+
+use signal_hook::{
+    consts::{SIGHUP, SIGINT, SIGQUIT, SIGTERM, SIGWINCH},
+    flag,
+    iterator::Signals,
+    low_level::emulate_default_handler,
+};
+
+use crate::{Event, Message, SignalMessage};
+
+/// Where signals are delivered to, which is the program that is currently running, if any.
+static RECEIVER: Mutex<Option<Sender<Message>>> = Mutex::new(None);
+/// Whether the thread listening for signals was started.
+static LISTENING: Mutex<bool> = Mutex::new(false);
+/// Which interrupts from the keyboard are kept from programs.
+static IGNORED: Mutex<Ignored> = Mutex::new(Ignored {
+    guards: 0,
+    arrived: None,
+    unseen: false,
+});
+
+struct Ignored {
+    /// The number of `IgnoreInterrupts` guards around.
+    guards: usize,
+    /// Set as soon as an interrupt arrives, before the thread listening for signals gets to it.
+    arrived: Option<Arc<AtomicBool>>,
+    /// Whether an interrupt arrived while they were ignored, which the listening thread has yet to come across.
+    unseen: bool,
+}
+
+/// Delivers signals to the given sender as `Event::Signal`s, until the returned guard is dropped.
+///
+/// Signal handlers are process wide and stay installed once registered, so a single thread listens for signals
+/// for as long as the process lives. Whenever no program is receiving them,
+/// it does what the signal would have done by default, like terminating the process.
+pub(crate) fn listen(msg_tx: Sender<Message>) -> Result<SignalGuard> {
+    let mut listening = LISTENING.lock().unwrap_or_else(|e| e.into_inner());
+    if !*listening {
+        // handlers run in the order they were registered, so this is set before the listening thread wakes up
+        let arrived = Arc::new(AtomicBool::new(false));
+        flag::register(SIGINT, arrived.clone())?;
+        flag::register(SIGQUIT, arrived.clone())?;
+        ignored().arrived = Some(arrived);
+        let mut signals = Signals::new([SIGINT, SIGQUIT, SIGTERM, SIGHUP, SIGWINCH])?;
+        thread::spawn(move || {
+            for signal in signals.forever() {
+                if matches!(signal, SIGINT | SIGQUIT) && is_ignored() {
+                    continue;
+                }
+                let receiver = RECEIVER.lock().unwrap_or_else(|e| e.into_inner()).clone();
+                let delivered = match (receiver, from_number(signal)) {
+                    (Some(msg_tx), Some(msg)) => msg_tx.send(Box::new(Event::Signal(msg))).is_ok(),
+                    _ => false,
+                };
+                if !delivered {
+                    let _ = emulate_default_handler(signal);
+                }
+            }
+        });
+        *listening = true;
+    }
+
+    let previous = RECEIVER
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .replace(msg_tx);
+    Ok(SignalGuard { previous })
+}
+
+/// Stops delivering signals to a program, once dropped.
+pub(crate) struct SignalGuard {
+    previous: Option<Sender<Message>>,
+}
+
+impl Drop for SignalGuard {
+    fn drop(&mut self) {
+        *RECEIVER.lock().unwrap_or_else(|e| e.into_inner()) = self.previous.take();
+    }
+}
+
+/// Keeps SIGINT and SIGQUIT from reaching programs, or ending the process, until dropped.
+///
+/// While a child process runs in the foreground, pressing Ctrl-C or Ctrl-\ is meant for the child,
+/// which gets the signal from the terminal too. Like shells and `system(3)` do, the program leaves it to the child.
+pub(crate) fn ignore_interrupts() -> IgnoreInterrupts {
+    let mut ignored = ignored();
+    ignored.guards += 1;
+    if let Some(arrived) = &ignored.arrived {
+        arrived.store(false, Ordering::SeqCst);
+    }
+    IgnoreInterrupts(())
+}
+
+/// Stops ignoring interrupts, once dropped. See `ignore_interrupts`.
+pub(crate) struct IgnoreInterrupts(());
+
+impl Drop for IgnoreInterrupts {
+    fn drop(&mut self) {
+        let mut ignored = ignored();
+        ignored.guards -= 1;
+        if ignored.guards == 0 {
+            // the child may well have ended because of an interrupt, which is still on its way to the listening thread
+            ignored.unseen = ignored
+                .arrived
+                .as_ref()
+                .is_some_and(|arrived| arrived.swap(false, Ordering::SeqCst));
+        }
+    }
+}
+
+/// Whether the interrupt the listening thread came across is ignored.
+fn is_ignored() -> bool {
+    let mut ignored = ignored();
+    if let Some(arrived) = &ignored.arrived {
+        arrived.store(false, Ordering::SeqCst);
+    }
+    let is_ignored = ignored.guards > 0 || ignored.unseen;
+    ignored.unseen = false;
+    is_ignored
+}
+
+fn ignored() -> MutexGuard<'static, Ignored> {
+    IGNORED.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+fn from_number(signal: i32) -> Option<SignalMessage> {
+    match signal {
+        SIGINT => Some(SignalMessage::Interrupt),
+        SIGTERM => Some(SignalMessage::Terminate),
+        SIGHUP => Some(SignalMessage::Hangup),
+        SIGWINCH => Some(SignalMessage::WindowChange),
+        _ => None,
+    }
+}
@@ -0,0 +1,301 @@
+use std::{
+    fmt,
+    marker::PhantomData,
+    sync::{
+        mpsc::{Receiver, RecvTimeoutError, Sender},
+        Arc, Mutex,
+    },
+    time::{Duration, Instant},
+};
+
+use crate::{
+    command::{erase, CancelToken},
+    runtime::{track, Origin},
+    Message,
+};
+
+/// Sends messages to a program from a long running source, like a subscription or a `command::stream`.
+///
+/// Its type parameter is the type of messages it sends, which is the app's own message type for `TypedApp`s.
+/// It can be cloned, and sent to other threads.
+pub struct Emitter<M = Message> {
+    msg_tx: Sender<Message>,
+    token: CancelToken,
+    /// The origin of the command the emitter was made for, if any.
+    origin: Origin,
+    msg: PhantomData<fn(M)>,
+}
+
+impl Emitter {
+    pub(crate) fn new(msg_tx: Sender<Message>, token: CancelToken, origin: Origin) -> Self {
+        Self {
+            msg_tx,
+            token,
+            origin,
+            msg: PhantomData,
+        }
+    }
+}
+
+impl<M: Send + 'static> Emitter<M> {
+    /// Sends a message to the program, which arrives in `update` like any other.
+    ///
+    /// Returns `false` if the emitter was stopped or the program has exited, in which case the message is dropped.
+    pub fn emit(&self, msg: M) -> bool {
+        !self.is_stopped() && self.send(msg)
+    }
+
+    /// Sends a message to the program, even if the emitter was stopped.
+    /// Returns `false` if the program has exited.
+    fn send(&self, msg: M) -> bool {
+        self.msg_tx
+            .send(track(erase(msg), self.origin.clone()))
+            .is_ok()
+    }
+}
+
+impl<M> Emitter<M> {
+    /// Whether the emitter was stopped. Once it is, there's no point in producing more messages.
+    pub fn is_stopped(&self) -> bool {
+        self.token.is_cancelled()
+    }
+
+    /// The token that is cancelled when the emitter is stopped.
+    /// Useful for waiting with `CancelToken::wait_timeout`.
+    pub fn token(&self) -> &CancelToken {
+        &self.token
+    }
+
+    /// The same emitter, for sending messages of another type. The runtime works with erased messages,
+    /// so this is how the typed versions of commands and subscriptions get their emitters.
+    pub(crate) fn retype<N>(self) -> Emitter<N> {
+        Emitter {
+            msg_tx: self.msg_tx,
+            token: self.token,
+            origin: self.origin,
+            msg: PhantomData,
+        }
+    }
+}
+
+impl<M> Clone for Emitter<M> {
+    fn clone(&self) -> Self {
+        Self {
+            msg_tx: self.msg_tx.clone(),
+            token: self.token.clone(),
+            origin: self.origin.clone(),
+            msg: PhantomData,
+        }
+    }
+}
+
+impl<M> fmt::Debug for Emitter<M> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Emitter")
+            .field("stopped", &self.is_stopped())
+            .finish_non_exhaustive()
+    }
+}
+
+type Start = Box<dyn FnOnce(Emitter) + Send + 'static>;
+
+/// A long lived source of messages, like a timer, a file watcher, or a channel.
+///
+/// Apps declare the subscriptions they want with `App::subscriptions`.
+/// Subscriptions are identified by their id: after every `update`, the program starts the ones with new ids,
+/// and stops the ones whose ids are no longer returned. Ones with the same id keep running undisturbed.
+///
+/// # Example
+///
+/// ```
+/// # use std::time::{Duration, Instant};
+/// # use rustea::{App, Command, Message, Subscription};
+/// struct Model {
+///     ticking: bool,
+/// }
+///
+/// struct TickMessage(Instant);
+///
+/// impl App for Model {
+///     fn update(&mut self, _msg: Message) -> Option<Command> {
+///         None
+///     }
+///
+///     fn view(&self) -> String {
+///         String::new()
+///     }
+///
+///     fn subscriptions(&self) -> Vec<Subscription> {
+///         if self.ticking {
+///             vec![Subscription::every("tick", Duration::from_secs(1), |at| {
+///                 Box::new(TickMessage(at))
+///             })]
+///         } else {
+///             vec![]
+///         }
+///     }
+/// }
+/// ```
+pub struct Subscription {
+    id: String,
+    start: Start,
+}
+
+impl Subscription {
+    /// A subscription that runs `f` on a dedicated thread once it starts.
+    ///
+    /// `f` sends its messages through the given `Emitter`, and should return once it is stopped.
+    pub fn new(id: impl Into<String>, f: impl FnOnce(Emitter) + Send + 'static) -> Self {
+        Self {
+            id: id.into(),
+            start: Box::new(f),
+        }
+    }
+
+    /// A subscription that dispatches the message made by `f` every time the given interval passes.
+    pub fn every(
+        id: impl Into<String>,
+        interval: Duration,
+        f: impl Fn(Instant) -> Message + Send + 'static,
+    ) -> Self {
+        Self::new(id, move |emitter| tick_every(interval, f, emitter))
+    }
+
+    /// A subscription that forwards everything received on the channel as a message.
+    ///
+    /// The receiver is shared, since `subscriptions` only borrows the app.
+    /// Once the subscription is stopped, whatever arrives is left on the channel,
+    /// for a subscription that takes its place.
+    pub fn channel(id: impl Into<String>, rx: Arc<Mutex<Receiver<Message>>>) -> Self {
+        Self::new(id, move |emitter| forward(&rx, emitter))
+    }
+
+    /// The id the subscription is identified by.
+    pub fn id(&self) -> &str {
+        &self.id
+    }
+
+    pub(crate) fn into_parts(self) -> (String, Start) {
+        (self.id, self.start)
+    }
+}
+
+/// Like `Subscription`, but with a message type of your own instead of `Message`, for `TypedApp::subscriptions`.
+pub struct TypedSubscription<M> {
+    subscription: Subscription,
+    msg: PhantomData<fn() -> M>,
+}
+
+impl<M: Send + 'static> TypedSubscription<M> {
+    /// The typed version of `Subscription::new`, whose emitter sends `M` values.
+    pub fn new(id: impl Into<String>, f: impl FnOnce(Emitter<M>) + Send + 'static) -> Self {
+        Self::from_untyped(Subscription::new(id, move |emitter| f(emitter.retype())))
+    }
+
+    /// The typed version of `Subscription::every`.
+    pub fn every(
+        id: impl Into<String>,
+        interval: Duration,
+        f: impl Fn(Instant) -> M + Send + 'static,
+    ) -> Self {
+        Self::new(id, move |emitter| tick_every(interval, f, emitter))
+    }
+
+    /// The typed version of `Subscription::channel`.
+    pub fn channel(id: impl Into<String>, rx: Arc<Mutex<Receiver<M>>>) -> Self {
+        Self::new(id, move |emitter| forward(&rx, emitter))
+    }
+
+    /// The id the subscription is identified by.
+    pub fn id(&self) -> &str {
+        self.subscription.id()
+    }
+
+    /// Wraps a plain `Subscription`.
+    ///
+    /// Any message it sends that isn't an `M` never reaches `update`.
+    pub fn from_untyped(subscription: Subscription) -> Self {
+        Self {
+            subscription,
+            msg: PhantomData,
+        }
+    }
+
+    /// Unwraps the untyped subscription.
+    pub fn into_untyped(self) -> Subscription {
+        self.subscription
+    }
+}
+
+fn tick_every<M: Send + 'static>(
+    interval: Duration,
+    f: impl Fn(Instant) -> M,
+    emitter: Emitter<M>,
+) {
+    let mut next = Instant::now();
+    loop {
+        next = match next.checked_add(interval) {
+            Some(next) => next,
+            // the next tick never comes, so there is only waiting to be stopped
+            None => {
+                emitter.token().wait_timeout(Duration::MAX);
+                return;
+            }
+        };
+        let timeout = next.saturating_duration_since(Instant::now());
+        if emitter.token().wait_timeout(timeout) || !emitter.emit(f(next)) {
+            return;
+        }
+    }
+}
+
+fn forward<M: Send + 'static>(rx: &Mutex<Receiver<M>>, emitter: Emitter<M>) {
+    loop {
+        let rx = rx.lock().unwrap_or_else(|e| e.into_inner());
+        // once stopped, messages are left on the receiver, for a subscription that replaces this one
+        if emitter.is_stopped() {
+            return;
+        }
+        // check for being stopped every now and then, even if nothing arrives
+        match rx.recv_timeout(Duration::from_millis(100)) {
+            // it may have been stopped while waiting, but the message was taken anyway,
+            // and nothing else would ever deliver it
+            Ok(msg) => {
+                if !emitter.send(msg) {
+                    return;
+                }
+            }
+            Err(RecvTimeoutError::Timeout) => (),
+            Err(RecvTimeoutError::Disconnected) => return,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{sync::mpsc, thread};
+
+    use super::*;
+
+    #[test]
+    fn stopped_channels_leave_messages_for_the_next_subscription() {
+        let (tx, rx) = mpsc::channel();
+        let rx = Mutex::new(rx);
+        let (msg_tx, msg_rx) = mpsc::channel();
+        let token = CancelToken::default();
+        let emitter = Emitter::new(msg_tx, token.clone(), Origin::default()).retype::<u32>();
+
+        thread::scope(|scope| {
+            let forwarding = scope.spawn(|| forward(&rx, emitter.clone()));
+            tx.send(1).unwrap();
+            msg_rx.recv_timeout(Duration::from_secs(5)).unwrap();
+            token.cancel();
+            forwarding.join().unwrap();
+        });
+
+        tx.send(2).unwrap();
+        forward(&rx, emitter);
+        assert!(msg_rx.try_recv().is_err());
+        assert_eq!(rx.lock().unwrap().try_recv(), Ok(2));
+    }
+}
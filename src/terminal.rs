@@ -0,0 +1,167 @@
+use std::{
+    io::{stdout, Result, Write},
+    panic,
+    sync::{Mutex, Once},
+    thread::{self, ThreadId},
+};
+
+use crossterm::{
+    cursor::{Hide, Show},
+    event::{DisableMouseCapture, EnableMouseCapture},
+    execute,
+    terminal::{disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen},
+};
+
+use crate::MouseMode;
+
+/// The terminal modes a program asks for.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct TerminalOptions {
+    pub(crate) raw_mode: bool,
+    pub(crate) alt_screen: bool,
+    pub(crate) mouse: MouseMode,
+}
+
+/// The modes currently applied to stdout, and the thread running the program that applied them,
+/// for the panic hook to undo.
+static STDOUT_MODES: Mutex<Option<(ThreadId, TerminalOptions)>> = Mutex::new(None);
+static PANIC_HOOK: Once = Once::new();
+
+/// Owns the program's output and the terminal state around it.
+///
+/// Entering optionally puts the terminal in raw mode, optionally switches to the alternate screen,
+/// hides the cursor, and enables mouse capture.
+/// Everything is undone when the terminal is restored, which happens automatically when it is dropped.
+/// This covers a normal quit as well as returning early with an error.
+/// For panics on the thread running the program, a hook is installed that restores the terminal
+/// before the panic message is printed, so that the message doesn't end up on the alternate screen.
+/// Panics on other threads are left alone, since they may well be caught, and the program keep running.
+/// The ones `rustea` catches itself end the program with an error, which drops the terminal.
+pub(crate) struct Terminal {
+    output: Box<dyn Write + Send>,
+    options: TerminalOptions,
+    is_stdout: bool,
+    active: bool,
+}
+
+impl Terminal {
+    /// Wraps the given output, or stdout if none is given.
+    pub(crate) fn new(output: Option<Box<dyn Write + Send>>, options: TerminalOptions) -> Self {
+        let is_stdout = output.is_none();
+        Self {
+            output: output.unwrap_or_else(|| Box::new(stdout())),
+            options,
+            is_stdout,
+            active: false,
+        }
+    }
+
+    /// Applies the terminal modes. Does nothing if they are already applied.
+    pub(crate) fn enter(&mut self) -> Result<()> {
+        if self.active {
+            return Ok(());
+        }
+
+        if self.is_stdout {
+            install_panic_hook();
+            *STDOUT_MODES.lock().unwrap_or_else(|e| e.into_inner()) =
+                Some((thread::current().id(), self.options));
+        }
+        self.active = true;
+
+        if self.options.raw_mode {
+            enable_raw_mode()?;
+        }
+        apply(&mut self.output, self.options)
+    }
+
+    /// Undoes the terminal modes. Does nothing if they aren't applied.
+    pub(crate) fn restore(&mut self) -> Result<()> {
+        if !self.active {
+            return Ok(());
+        }
+
+        if self.is_stdout {
+            *STDOUT_MODES.lock().unwrap_or_else(|e| e.into_inner()) = None;
+        }
+        self.active = false;
+
+        unapply(&mut self.output, self.options)?;
+        if self.options.raw_mode {
+            disable_raw_mode()?;
+        }
+        Ok(())
+    }
+}
+
+impl Write for Terminal {
+    fn write(&mut self, buf: &[u8]) -> Result<usize> {
+        self.output.write(buf)
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        self.output.flush()
+    }
+}
+
+impl Drop for Terminal {
+    fn drop(&mut self) {
+        let _ = self.restore();
+    }
+}
+
+fn apply(output: &mut impl Write, options: TerminalOptions) -> Result<()> {
+    if options.alt_screen {
+        execute!(output, EnterAlternateScreen)?;
+    }
+    match options.mouse {
+        MouseMode::None => (),
+        MouseMode::CellMotion => execute!(output, EnableCellMotion)?,
+        MouseMode::AllMotion => execute!(output, EnableMouseCapture)?,
+    }
+    execute!(output, Hide)
+}
+
+fn unapply(output: &mut impl Write, options: TerminalOptions) -> Result<()> {
+    if options.mouse != MouseMode::None {
+        execute!(output, DisableMouseCapture)?;
+    }
+    execute!(output, Show)?;
+    if options.alt_screen {
+        execute!(output, LeaveAlternateScreen)?;
+    }
+    Ok(())
+}
+
+fn install_panic_hook() {
+    PANIC_HOOK.call_once(|| {
+        let previous = panic::take_hook();
+        panic::set_hook(Box::new(move |info| {
+            // `try_lock`, since the panic may have happened while the lock was held.
+            if let Ok(mut modes) = STDOUT_MODES.try_lock() {
+                let current = thread::current().id();
+                if let Some((_, options)) = modes.take_if(|(thread, _)| *thread == current) {
+                    let _ = unapply(&mut stdout(), options);
+                    if options.raw_mode {
+                        let _ = disable_raw_mode();
+                    }
+                }
+            }
+            previous(info);
+        }));
+    });
+}
+
+/// Like crossterm's `EnableMouseCapture`, but without reporting motion when no buttons are held.
+struct EnableCellMotion;
+
+impl crossterm::Command for EnableCellMotion {
+    fn write_ansi(&self, f: &mut impl std::fmt::Write) -> std::fmt::Result {
+        f.write_str("\x1b[?1000h\x1b[?1002h\x1b[?1015h\x1b[?1006h")
+    }
+
+    #[cfg(windows)]
+    fn execute_winapi(&self) -> crossterm::Result<()> {
+        crossterm::Command::execute_winapi(&EnableMouseCapture)
+    }
+}
@@ -0,0 +1,561 @@
+//! Tools for testing apps end to end, without a terminal.
+//!
+//! A `TestProgram` runs an app like `Program` does, but against a virtual terminal of a given size.
+//! Tests feed it events and messages, and then make assertions about the app, or the frame it renders.
+//! Frames can also be compared with golden files, using `assert_frame_snapshot!`.
+//!
+//! # Example
+//!
+//! ```
+//! use rustea::{
+//!     command,
+//!     crossterm::event::{KeyCode, KeyEvent},
+//!     testing::TestProgram,
+//!     App, Command, Message,
+//! };
+//!
+//! struct Counter(i32);
+//!
+//! impl App for Counter {
+//!     fn update(&mut self, msg: Message) -> Option<Command> {
+//!         if let Some(key_event) = msg.downcast_ref::<KeyEvent>() {
+//!             match key_event.code {
+//!                 KeyCode::Up => self.0 += 1,
+//!                 KeyCode::Down => self.0 -= 1,
+//!                 KeyCode::Esc => return Some(Box::new(command::quit)),
+//!                 _ => (),
+//!             }
+//!         }
+//!         None
+//!     }
+//!
+//!     fn view(&self) -> String {
+//!         format!("Count: {}", self.0)
+//!     }
+//! }
+//!
+//! let mut program = TestProgram::synchronous(Counter(0), 20, 5);
+//! program.press(KeyCode::Up);
+//! program.press(KeyCode::Up);
+//! program.press(KeyCode::Down);
+//! assert_eq!(program.frame(), "Count: 1");
+//!
+//! program.press(KeyCode::Esc);
+//! assert_eq!(program.exit_code(), Some(0));
+//! ```
+
+use std::{
+    env, fs, io,
+    path::Path,
+    sync::mpsc::{self, Receiver, RecvTimeoutError, Sender},
+    time::{Duration, Instant},
+};
+
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers, MouseEvent};
+
+use crate::{
+    command::{self, ExecFinished},
+    pool::{CommandMetrics, ThreadPool},
+    program::DEFAULT_WORKERS,
+    renderer::clip_line,
+    runtime::{Flow, Runtime},
+    scheduler::{Deterministic, Scheduler, Threaded},
+    Event, Message, TypedApp,
+};
+
+/// Runs an app against a virtual terminal, for tests.
+///
+/// Events and messages are handled right away, on the test's thread, and so are the messages that are already
+/// waiting once they have been. How commands are executed depends on how the program was made:
+///
+/// - With `new`, commands are executed on worker threads and timers fire by the system clock,
+///   like they do in a `Program`. Their messages may arrive later; `wait_until` waits for them.
+/// - With `synchronous`, commands are executed on the test's thread, one at a time and in order,
+///   until there is nothing left to do. Timers fire by a virtual clock instead, which only moves with `advance`.
+///   This makes tests deterministic, as long as the app doesn't rely on subscriptions or threads of its own.
+/// - With `stepped`, commands are executed like with `synchronous`, but only when the test asks for it,
+///   with `step` or `run_until_idle`. This allows checking on the app in between.
+///
+/// Whatever `update` does is the same as it would be in a real program. The few things that need a real terminal
+/// are simulated: `command::suspend` delivers a `SuspendMessage` followed right away by a `ResumeMessage`,
+/// and lines from `command::println` are collected, see `printed`. Processes from `command::exec` are run for real.
+///
+/// If a command panics, or the program runs into an error, the test panics with it.
+pub struct TestProgram<A: TypedApp> {
+    runtime: Runtime<A>,
+    msg_tx: Sender<Message>,
+    msg_rx: Receiver<Message>,
+    /// The scheduler, when commands are executed deterministically.
+    scheduler: Option<Deterministic>,
+    /// Whether to run until idle after delivering a message.
+    automatic: bool,
+    size: (u16, u16),
+    printed: Vec<String>,
+    exit_code: Option<i32>,
+}
+
+impl<A: TypedApp> TestProgram<A> {
+    /// Starts the app in a virtual terminal of the given size, executing commands on worker threads.
+    pub fn new(app: A, width: u16, height: u16) -> Self {
+        let (msg_tx, msg_rx) = mpsc::channel();
+        let pool = ThreadPool::new(DEFAULT_WORKERS, CommandMetrics::default());
+        let scheduler = Threaded::new(pool, msg_tx.clone());
+        Self::start(
+            app,
+            (width, height),
+            Box::new(scheduler),
+            None,
+            true,
+            (msg_tx, msg_rx),
+        )
+    }
+
+    /// Starts the app in a virtual terminal of the given size,
+    /// executing commands on the current thread, one at a time, whenever messages are handled.
+    pub fn synchronous(app: A, width: u16, height: u16) -> Self {
+        Self::deterministic(app, (width, height), true)
+    }
+
+    /// Starts the app in a virtual terminal of the given size,
+    /// executing commands on the current thread, one at a time, only once `step` or `run_until_idle` is called.
+    ///
+    /// Commands returned from `init` are queued too.
+    pub fn stepped(app: A, width: u16, height: u16) -> Self {
+        Self::deterministic(app, (width, height), false)
+    }
+
+    fn deterministic(app: A, size: (u16, u16), automatic: bool) -> Self {
+        let (msg_tx, msg_rx) = mpsc::channel();
+        let scheduler = Deterministic::new(msg_tx.clone());
+        Self::start(
+            app,
+            size,
+            Box::new(scheduler.clone()),
+            Some(scheduler),
+            automatic,
+            (msg_tx, msg_rx),
+        )
+    }
+
+    fn start(
+        app: A,
+        size: (u16, u16),
+        runtime_scheduler: Box<dyn Scheduler>,
+        scheduler: Option<Deterministic>,
+        automatic: bool,
+        (msg_tx, msg_rx): (Sender<Message>, Receiver<Message>),
+    ) -> Self {
+        let mut program = Self {
+            runtime: Runtime::new(app, runtime_scheduler, msg_tx.clone()),
+            msg_tx,
+            msg_rx,
+            scheduler,
+            automatic,
+            size,
+            printed: Vec::new(),
+            exit_code: None,
+        };
+        program.runtime.init();
+        if automatic {
+            program.run_until_idle();
+        }
+        program
+    }
+
+    /// Delivers a message of the app's own type to `update`.
+    /// For apps implementing `App`, this is any boxed message.
+    pub fn send(&mut self, msg: A::Msg) {
+        self.deliver(command::erase(msg));
+    }
+
+    /// Delivers any of the events `rustea` produces to `update`.
+    /// Resize events also change the size of the virtual terminal.
+    pub fn event(&mut self, event: Event) {
+        if let Event::Resize(width, height) = event {
+            self.size = (width, height);
+        }
+        self.deliver(Box::new(event));
+    }
+
+    /// Delivers a key event.
+    pub fn key(&mut self, event: KeyEvent) {
+        self.event(Event::Key(event));
+    }
+
+    /// Delivers a key press without any modifiers.
+    pub fn press(&mut self, code: KeyCode) {
+        self.key(KeyEvent::new(code, KeyModifiers::NONE));
+    }
+
+    /// Delivers a key press for every character of the text, as if it was typed.
+    pub fn type_text(&mut self, text: &str) {
+        for c in text.chars() {
+            self.press(KeyCode::Char(c));
+        }
+    }
+
+    /// Delivers a mouse event.
+    pub fn mouse(&mut self, event: MouseEvent) {
+        self.event(Event::Mouse(event));
+    }
+
+    /// Resizes the virtual terminal, and delivers the resize event.
+    pub fn resize(&mut self, width: u16, height: u16) {
+        self.event(Event::Resize(width, height));
+    }
+
+    /// Handles the next message that is waiting, like one sent by a command that has finished.
+    /// If there is none, executes the next queued command instead, for programs that execute commands
+    /// on the test's thread. Returns `false` if there was nothing to do, or the app has quit.
+    pub fn step(&mut self) -> bool {
+        if self.exit_code.is_some() {
+            return false;
+        }
+
+        if let Ok(msg) = self.msg_rx.try_recv() {
+            self.handle(msg);
+            true
+        } else {
+            self.scheduler
+                .as_ref()
+                .is_some_and(|scheduler| scheduler.run_next())
+        }
+    }
+
+    /// Steps until there is nothing left to do: every waiting message has been handled,
+    /// and every queued command has been executed.
+    ///
+    /// For programs made with `new`, commands still running on other threads aren't waited for.
+    pub fn run_until_idle(&mut self) {
+        while self.step() {}
+    }
+
+    /// Moves the virtual clock forward, firing the timers that become due along the way, in order.
+    /// For programs made with `synchronous`, the program runs until idle after each of them.
+    ///
+    /// # Panics
+    ///
+    /// Panics for programs made with `new`, whose timers fire by the system clock.
+    pub fn advance(&mut self, duration: Duration) {
+        let scheduler = match &self.scheduler {
+            Some(scheduler) => scheduler.clone(),
+            None => {
+                panic!("only the clock of a synchronous or stepped TestProgram can be advanced")
+            }
+        };
+
+        let until = scheduler.now().checked_add(duration);
+        while scheduler.fire_next(until) {
+            if self.automatic {
+                self.run_until_idle();
+            }
+        }
+    }
+
+    /// Handles messages as they arrive, until `done` returns `true` for the app, or the timeout passes.
+    /// Returns whether `done` returned `true`.
+    ///
+    /// This is how tests wait for commands running on other threads, timers going by the system clock,
+    /// and subscriptions. The program runs until idle in between.
+    pub fn wait_until(&mut self, timeout: Duration, mut done: impl FnMut(&A) -> bool) -> bool {
+        // `None` when the timeout is too long to be represented, which is as good as waiting forever
+        let deadline = Instant::now().checked_add(timeout);
+        loop {
+            self.run_until_idle();
+            if done(self.app()) {
+                return true;
+            }
+            if self.exit_code.is_some() {
+                return false;
+            }
+
+            let remaining = deadline.map_or(Duration::MAX, |deadline| {
+                deadline.saturating_duration_since(Instant::now())
+            });
+            match self.msg_rx.recv_timeout(remaining) {
+                Ok(msg) => self.handle(msg),
+                Err(RecvTimeoutError::Timeout) => return false,
+                // the runtime holds a sender, so this can't happen
+                Err(RecvTimeoutError::Disconnected) => return false,
+            }
+        }
+    }
+
+    /// The app, as it is right now.
+    pub fn app(&self) -> &A {
+        self.runtime.app()
+    }
+
+    /// Stops everything still running in the background, and gives back the app.
+    pub fn into_app(self) -> A {
+        self.runtime.into_app()
+    }
+
+    /// The size of the virtual terminal, (width, height).
+    pub fn size(&self) -> (u16, u16) {
+        self.size
+    }
+
+    /// The frame as the virtual terminal would show it, as plain text:
+    /// the lines of `view` that fit on the screen, cut off at its width, with styles like colors left out.
+    pub fn frame(&self) -> String {
+        self.clip(false)
+    }
+
+    /// Like `frame`, but with the escape sequences for styles like colors left in.
+    pub fn styled_frame(&self) -> String {
+        self.clip(true)
+    }
+
+    /// Every line printed with `command::println` so far.
+    pub fn printed(&self) -> &[String] {
+        &self.printed
+    }
+
+    /// The exit code, once the app has quit. Once it has, no more messages are handled.
+    pub fn exit_code(&self) -> Option<i32> {
+        self.exit_code
+    }
+
+    fn deliver(&mut self, msg: Message) {
+        self.handle(msg);
+        if self.automatic {
+            self.run_until_idle();
+        }
+    }
+
+    fn handle(&mut self, msg: Message) {
+        if self.exit_code.is_some() {
+            return;
+        }
+
+        let flow = match self.runtime.handle(msg) {
+            Ok(flow) => flow,
+            Err(err) => panic!("the program failed: {}", err),
+        };
+        match flow {
+            Flow::Continue => (),
+            Flow::Print(text) => self.printed.push(text),
+            Flow::Suspend => {
+                // there is no process to stop, so it resumes right away
+                self.handle(Box::new(Event::Suspend));
+                self.handle(Box::new(Event::Resume));
+            }
+            Flow::Exec(mut process, done) => {
+                let status = process.status();
+                let _ = self.msg_tx.send(done(ExecFinished { status }));
+            }
+            Flow::Quit(code) => self.exit_code = Some(code),
+        }
+    }
+
+    fn clip(&self, styled: bool) -> String {
+        let (width, height) = self.size;
+        self.app()
+            .view()
+            .split('\n')
+            .take(usize::from(height))
+            .map(|line| clip_line(line.strip_suffix('\r').unwrap_or(line), width, styled))
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+}
+
+/// Asserts that the frame of a `TestProgram` matches a snapshot, which is a golden file checked in
+/// under `tests/snapshots` in the crate being tested.
+///
+/// `assert_frame_snapshot!(program, "name")` compares the plain text `frame` with `tests/snapshots/name.snap`.
+/// `assert_frame_snapshot!(program, "name", styled)` compares the `styled_frame` with
+/// `tests/snapshots/name.styled.snap` instead, where escape characters are written as `\x1b`, to keep it readable.
+///
+/// When the frame doesn't match, the assertion fails with a line by line diff.
+/// To create or update snapshots, run the tests with the `RUSTEA_UPDATE_SNAPSHOTS` environment variable set to `1`,
+/// and review the changes to the files before checking them in.
+///
+/// # Example
+///
+/// ```no_run
+/// # use rustea::{App, Command, Message};
+/// # struct Menu;
+/// # impl App for Menu {
+/// #     fn update(&mut self, _msg: Message) -> Option<Command> { None }
+/// #     fn view(&self) -> String { String::new() }
+/// # }
+/// use rustea::{assert_frame_snapshot, crossterm::event::KeyCode, testing::TestProgram};
+///
+/// let mut program = TestProgram::synchronous(Menu, 40, 10);
+/// program.press(KeyCode::Down);
+/// assert_frame_snapshot!(program, "menu_second_item");
+/// assert_frame_snapshot!(program, "menu_second_item", styled);
+/// ```
+#[macro_export]
+macro_rules! assert_frame_snapshot {
+    ($program:expr, $name:expr $(,)?) => {
+        $crate::testing::check_snapshot(
+            ::std::env!("CARGO_MANIFEST_DIR"),
+            $name,
+            &$program.frame(),
+            false,
+        )
+    };
+    ($program:expr, $name:expr, styled $(,)?) => {
+        $crate::testing::check_snapshot(
+            ::std::env!("CARGO_MANIFEST_DIR"),
+            $name,
+            &$program.styled_frame(),
+            true,
+        )
+    };
+}
+
+/// The environment variable that makes `assert_frame_snapshot!` write snapshots, instead of comparing with them.
+const UPDATE_SNAPSHOTS: &str = "RUSTEA_UPDATE_SNAPSHOTS";
+
+/// The implementation of `assert_frame_snapshot!`.
+#[doc(hidden)]
+#[track_caller]
+pub fn check_snapshot(manifest_dir: &str, name: &str, frame: &str, styled: bool) {
+    let extension = if styled { "styled.snap" } else { "snap" };
+    let path = Path::new(manifest_dir)
+        .join("tests")
+        .join("snapshots")
+        .join(format!("{}.{}", name, extension));
+    let actual = if styled {
+        frame.replace('\x1b', "\\x1b")
+    } else {
+        frame.to_string()
+    };
+
+    if env::var_os(UPDATE_SNAPSHOTS).is_some_and(|value| value == "1") {
+        let written = path
+            .parent()
+            .map_or(Ok(()), fs::create_dir_all)
+            .and_then(|_| fs::write(&path, format!("{}\n", actual)));
+        if let Err(err) = written {
+            panic!("couldn't write snapshot {}: {}", path.display(), err);
+        }
+        return;
+    }
+
+    let expected = match fs::read_to_string(&path) {
+        Ok(expected) => expected.replace("\r\n", "\n"),
+        Err(err) if err.kind() == io::ErrorKind::NotFound => panic!(
+            "snapshot {} doesn't exist yet, run the tests with {}=1 to create it\n\nthe frame was:\n{}",
+            path.display(),
+            UPDATE_SNAPSHOTS,
+            actual
+        ),
+        Err(err) => panic!("couldn't read snapshot {}: {}", path.display(), err),
+    };
+    let expected = expected.strip_suffix('\n').unwrap_or(&expected);
+
+    if expected != actual {
+        panic!(
+            "frame doesn't match snapshot {}\n\n{}\nrun the tests with {}=1 to update it",
+            path.display(),
+            diff(expected, &actual),
+            UPDATE_SNAPSHOTS
+        );
+    }
+}
+
+/// A line by line diff, with removed lines prefixed with `-`, added lines with `+`, and unchanged ones with a space.
+fn diff(expected: &str, actual: &str) -> String {
+    let old: Vec<&str> = expected.split('\n').collect();
+    let new: Vec<&str> = actual.split('\n').collect();
+
+    // lengths of the longest common subsequences of the suffixes
+    let mut lcs = vec![vec![0; new.len() + 1]; old.len() + 1];
+    for i in (0..old.len()).rev() {
+        for j in (0..new.len()).rev() {
+            lcs[i][j] = if old[i] == new[j] {
+                lcs[i + 1][j + 1] + 1
+            } else {
+                lcs[i + 1][j].max(lcs[i][j + 1])
+            };
+        }
+    }
+
+    let mut out = String::new();
+    let (mut i, mut j) = (0, 0);
+    while i < old.len() || j < new.len() {
+        if i < old.len() && j < new.len() && old[i] == new[j] {
+            out.push_str(&format!("  {}\n", old[i]));
+            i += 1;
+            j += 1;
+        } else if i < old.len() && (j == new.len() || lcs[i + 1][j] >= lcs[i][j + 1]) {
+            out.push_str(&format!("- {}\n", old[i]));
+            i += 1;
+        } else {
+            out.push_str(&format!("+ {}\n", new[j]));
+            j += 1;
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{panic, process};
+
+    use super::*;
+
+    /// Runs `f`, which is expected to panic, and returns the panic message.
+    fn panic_message(f: impl FnOnce()) -> String {
+        let payload = panic::catch_unwind(panic::AssertUnwindSafe(f)).unwrap_err();
+        match payload.downcast::<String>() {
+            Ok(message) => *message,
+            Err(payload) => payload.downcast_ref::<&str>().unwrap().to_string(),
+        }
+    }
+
+    #[test]
+    fn diff_lines() {
+        assert_eq!(diff("a\nb\nc", "a\nb\nc"), "  a\n  b\n  c\n");
+        assert_eq!(diff("a\nc", "a\nb\nc"), "  a\n+ b\n  c\n");
+        assert_eq!(diff("a\nb\nc", "a\nc"), "  a\n- b\n  c\n");
+        assert_eq!(diff("a\nb\nc", "a\nx\nc"), "  a\n- b\n+ x\n  c\n");
+        assert_eq!(diff("a\nb", "b\nc"), "- a\n  b\n+ c\n");
+        assert_eq!(diff("", "a"), "- \n+ a\n");
+    }
+
+    #[test]
+    fn snapshots() {
+        let dir = env::temp_dir().join(format!("rustea-snapshots-{}", process::id()));
+        let dir_str = dir.to_str().unwrap();
+        let snapshots = dir.join("tests").join("snapshots");
+        let frame = "Title\n\x1b[1mbold\x1b[0m";
+
+        let message = panic_message(|| check_snapshot(dir_str, "frame", frame, false));
+        assert!(message.contains("doesn't exist yet"), "{}", message);
+        assert!(message.contains(UPDATE_SNAPSHOTS), "{}", message);
+        assert!(message.ends_with(frame), "{}", message);
+
+        env::set_var(UPDATE_SNAPSHOTS, "1");
+        check_snapshot(dir_str, "frame", frame, false);
+        check_snapshot(dir_str, "frame", frame, true);
+        env::remove_var(UPDATE_SNAPSHOTS);
+        assert_eq!(
+            fs::read_to_string(snapshots.join("frame.snap")).unwrap(),
+            "Title\n\x1b[1mbold\x1b[0m\n"
+        );
+        assert_eq!(
+            fs::read_to_string(snapshots.join("frame.styled.snap")).unwrap(),
+            "Title\n\\x1b[1mbold\\x1b[0m\n"
+        );
+        check_snapshot(dir_str, "frame", frame, false);
+        check_snapshot(dir_str, "frame", frame, true);
+
+        // snapshots checked out with CRLF line endings, or without a trailing newline, still match
+        fs::write(snapshots.join("lines.snap"), "a\r\nb\r\n").unwrap();
+        check_snapshot(dir_str, "lines", "a\nb", false);
+        fs::write(snapshots.join("lines.snap"), "a\nb").unwrap();
+        check_snapshot(dir_str, "lines", "a\nb", false);
+
+        let message = panic_message(|| check_snapshot(dir_str, "lines", "a\nc", false));
+        assert!(message.contains("doesn't match"), "{}", message);
+        assert!(message.contains("  a\n- b\n+ c\n"), "{}", message);
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,195 @@
+use std::{
+    cmp::Ordering,
+    collections::BinaryHeap,
+    panic::{self, AssertUnwindSafe},
+    sync::{mpsc::Sender, Arc, Condvar, Mutex, MutexGuard},
+    thread,
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+
+use crate::{runtime::CommandPanicked, Message};
+
+pub(crate) type Fire = Box<dyn FnOnce(Instant) -> Message + Send + 'static>;
+
+/// Delivers messages at given instants, all from a single thread.
+///
+/// The thread is started the first time something is scheduled, and exits once the timer is dropped.
+pub(crate) struct Timer {
+    shared: Arc<Shared>,
+    msg_tx: Sender<Message>,
+    started: bool,
+}
+
+struct Shared {
+    state: Mutex<State>,
+    changed: Condvar,
+}
+
+struct State {
+    queue: TimerQueue,
+    closed: bool,
+}
+
+/// Timers ordered by deadline.
+#[derive(Default)]
+pub(crate) struct TimerQueue {
+    entries: BinaryHeap<Entry>,
+    /// Timers whose deadline is too far away to be represented, which never fire.
+    /// They are kept, so that whatever waits on them, like a sequence, keeps waiting.
+    never: Vec<Fire>,
+    // breaks ties between equal deadlines, so entries fire in the order they were scheduled
+    seq: u64,
+}
+
+struct Entry {
+    deadline: Instant,
+    seq: u64,
+    fire: Fire,
+}
+
+impl Timer {
+    pub(crate) fn new(msg_tx: Sender<Message>) -> Self {
+        Self {
+            shared: Arc::new(Shared {
+                state: Mutex::new(State {
+                    queue: TimerQueue::default(),
+                    closed: false,
+                }),
+                changed: Condvar::new(),
+            }),
+            msg_tx,
+            started: false,
+        }
+    }
+
+    /// Sends the message made by `fire` once `deadline` has passed. Without a deadline, it is never sent.
+    pub(crate) fn schedule(&mut self, deadline: Option<Instant>, fire: Fire) {
+        if !self.started {
+            self.started = true;
+            let shared = self.shared.clone();
+            let msg_tx = self.msg_tx.clone();
+            thread::spawn(move || shared.run(&msg_tx));
+        }
+
+        self.shared.lock().queue.push(deadline, fire);
+        self.shared.changed.notify_one();
+    }
+}
+
+impl Drop for Timer {
+    fn drop(&mut self) {
+        self.shared.lock().closed = true;
+        self.shared.changed.notify_one();
+    }
+}
+
+impl Shared {
+    fn lock(&self) -> MutexGuard<'_, State> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn run(&self, msg_tx: &Sender<Message>) {
+        let mut state = self.lock();
+        loop {
+            if state.closed {
+                return;
+            }
+
+            let now = Instant::now();
+            if let Some(fire) = state.queue.pop_due(now) {
+                drop(state);
+                if msg_tx.send(fire_at(fire, now)).is_err() {
+                    return;
+                }
+                state = self.lock();
+                continue;
+            }
+
+            match state.queue.next_deadline() {
+                Some(deadline) => {
+                    let timeout = deadline - now;
+                    state = self
+                        .changed
+                        .wait_timeout(state, timeout)
+                        .unwrap_or_else(|e| e.into_inner())
+                        .0;
+                }
+                None => state = self.changed.wait(state).unwrap_or_else(|e| e.into_inner()),
+            }
+        }
+    }
+}
+
+impl TimerQueue {
+    /// Adds a timer. Without a deadline, it never fires.
+    pub(crate) fn push(&mut self, deadline: Option<Instant>, fire: Fire) {
+        let deadline = match deadline {
+            Some(deadline) => deadline,
+            None => return self.never.push(fire),
+        };
+        let seq = self.seq;
+        self.seq += 1;
+        self.entries.push(Entry {
+            deadline,
+            seq,
+            fire,
+        });
+    }
+
+    /// Removes the earliest timer, if its deadline is no later than `now`.
+    pub(crate) fn pop_due(&mut self, now: Instant) -> Option<Fire> {
+        match self.entries.peek() {
+            Some(entry) if entry.deadline <= now => self.entries.pop().map(|entry| entry.fire),
+            _ => None,
+        }
+    }
+
+    pub(crate) fn next_deadline(&self) -> Option<Instant> {
+        self.entries.peek().map(|entry| entry.deadline)
+    }
+}
+
+/// Makes the message of a timer that fired at the given instant.
+/// If making it panics, the panic is reported instead, like for commands.
+pub(crate) fn fire_at(fire: Fire, at: Instant) -> Message {
+    match panic::catch_unwind(AssertUnwindSafe(|| fire(at))) {
+        Ok(msg) => msg,
+        Err(payload) => Box::new(CommandPanicked::from_payload(payload)),
+    }
+}
+
+// `BinaryHeap` is a max heap, so the earliest deadline has to compare as the greatest
+impl Ord for Entry {
+    fn cmp(&self, other: &Self) -> Ordering {
+        (other.deadline, other.seq).cmp(&(self.deadline, self.seq))
+    }
+}
+
+impl PartialOrd for Entry {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl PartialEq for Entry {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Entry {}
+
+/// How long until the system clock reaches the next multiple of `interval`.
+pub(crate) fn until_boundary(interval: Duration) -> Duration {
+    let since_epoch = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default();
+    until_next_multiple(interval, since_epoch)
+}
+
+/// How long it takes from `elapsed` to the next multiple of `interval`.
+pub(crate) fn until_next_multiple(interval: Duration, elapsed: Duration) -> Duration {
+    let interval = interval.as_nanos().max(1);
+    let remaining = interval - elapsed.as_nanos() % interval;
+    Duration::from_nanos(remaining.try_into().unwrap_or(u64::MAX))
+}
@@ -33,25 +33,19 @@ impl Input {
     /// * Right. Steps the pos forward if possible.
     pub fn on_key_event(&mut self, key_event: KeyEvent) {
         match key_event.code {
-            KeyCode::Backspace => {
-                if self.pos > 0 {
-                    self.buffer.remove(self.pos - 1);
-                    self.pos -= 1;
-                }
+            KeyCode::Backspace if self.pos > 0 => {
+                self.buffer.remove(self.pos - 1);
+                self.pos -= 1;
             }
             KeyCode::Char(c) => {
                 self.buffer.insert(self.pos, c);
                 self.pos += 1;
             }
-            KeyCode::Left => {
-                if self.pos > 0 {
-                    self.pos -= 1;
-                }
+            KeyCode::Left if self.pos > 0 => {
+                self.pos -= 1;
             }
-            KeyCode::Right => {
-                if self.pos < self.buffer.len() {
-                    self.pos += 1;
-                }
+            KeyCode::Right if self.pos < self.buffer.len() => {
+                self.pos += 1;
             }
             _ => (),
         }